@@ -1,6 +1,12 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
 use std::io::Cursor;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
 /// used to convert [`Cacheable`] to bytes and vice versa.
@@ -24,6 +30,103 @@ pub trait Cacheable: Debug {
         let bytes = hex::decode(hex)?;
         Self::from_bytes(&bytes)
     }
+
+    /// The name used to identify this type in type-tagged values.
+    /// See [`crate::TypeTagged`].
+    ///
+    /// Defaults to [`std::any::type_name`], which is not guaranteed to be
+    /// stable across compiler versions. Override it if tagged values
+    /// must survive a toolchain upgrade.
+    fn type_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        std::any::type_name::<Self>()
+    }
+
+    /// The schema version of the encoding produced by [`Cacheable::to_bytes`].
+    ///
+    /// Bump it when the encoding changes. Defaults to `0`.
+    fn schema_version() -> u32
+    where
+        Self: Sized,
+    {
+        0
+    }
+
+    /// Whether [`Cacheable::from_bytes`] can decode a value encoded with schema `version`.
+    ///
+    /// Only the current [`Cacheable::schema_version`] by default.
+    /// [`crate::Versioned`] accepts older versions too, as it upgrades them.
+    fn accepts_schema_version(version: u32) -> bool
+    where
+        Self: Sized,
+    {
+        version == Self::schema_version()
+    }
+
+    /// Convert [`Cacheable`] to [`bytes::Bytes`].
+    ///
+    /// Feature `bytes` must be enabled. By default, it wraps [`Cacheable::to_bytes`].
+    /// Types that own their bytes (such as [`bytes::Bytes`], `Vec<u8>` and [`String`])
+    /// override it to avoid copying.
+    #[cfg(feature = "bytes")]
+    fn into_buf(self) -> bytes::Bytes
+    where
+        Self: Sized,
+    {
+        bytes::Bytes::from(self.to_bytes())
+    }
+
+    /// Convert [`bytes::Bytes`] to [`Cacheable`].
+    ///
+    /// Feature `bytes` must be enabled. By default, it calls [`Cacheable::from_bytes`].
+    #[cfg(feature = "bytes")]
+    fn from_buf(buf: bytes::Bytes) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::from_bytes(&buf)
+    }
+
+    /// Convert a slice of [`Cacheable`] to bytes. Used by `Vec<T>` and `[T; N]`.
+    ///
+    /// By default, it is length-prefixed. `u8` overrides it,
+    /// so that `Vec<u8>` is stored as raw bytes.
+    #[doc(hidden)]
+    fn slice_to_bytes(items: &[Self]) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut wtr = Writer::with_count(items.len());
+        items.iter().for_each(|item| wtr.item(item));
+        wtr.finish()
+    }
+
+    /// Convert bytes to a [`Vec`] of [`Cacheable`]. See [`Cacheable::slice_to_bytes`].
+    #[doc(hidden)]
+    fn vec_from_bytes(bytes: &[u8]) -> anyhow::Result<Vec<Self>>
+    where
+        Self: Sized,
+    {
+        let mut rdr = Reader::new(bytes);
+        let ret = (0..rdr.count()?)
+            .map(|_| rdr.item())
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        rdr.finish()?;
+
+        Ok(ret)
+    }
+
+    /// Convert a [`Vec`] of [`Cacheable`] to [`bytes::Bytes`]. See [`Cacheable::into_buf`].
+    #[cfg(feature = "bytes")]
+    #[doc(hidden)]
+    fn vec_into_buf(items: Vec<Self>) -> bytes::Bytes
+    where
+        Self: Sized,
+    {
+        bytes::Bytes::from(Self::slice_to_bytes(&items))
+    }
 }
 
 impl Cacheable for () {
@@ -47,18 +150,43 @@ impl<T: Cacheable> Cacheable for Arc<T> {
     {
         Ok(Arc::new(T::from_bytes(bytes)?))
     }
+
+    #[cfg(feature = "bytes")]
+    fn from_buf(buf: bytes::Bytes) -> anyhow::Result<Self> {
+        Ok(Arc::new(T::from_buf(buf)?))
+    }
+
+    fn type_name() -> &'static str {
+        T::type_name()
+    }
+
+    fn schema_version() -> u32 {
+        T::schema_version()
+    }
+
+    fn accepts_schema_version(version: u32) -> bool {
+        T::accepts_schema_version(version)
+    }
 }
 
-impl Cacheable for Vec<u8> {
+/// `Vec<u8>` is stored as raw bytes. Other `Vec<T>` are length-prefixed.
+///
+/// Lengths are stored as `u32`, so collections and their items must be smaller than 4 GiB.
+impl<T: Cacheable> Cacheable for Vec<T> {
     fn to_bytes(&self) -> Vec<u8> {
-        self.clone()
+        T::slice_to_bytes(self)
     }
 
     fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
     where
         Self: Sized,
     {
-        Ok(bytes.to_vec())
+        T::vec_from_bytes(bytes)
+    }
+
+    #[cfg(feature = "bytes")]
+    fn into_buf(self) -> bytes::Bytes {
+        T::vec_into_buf(self)
     }
 }
 
@@ -73,10 +201,41 @@ impl Cacheable for String {
     {
         Ok(Self::from_utf8(bytes.to_vec())?)
     }
+
+    #[cfg(feature = "bytes")]
+    fn into_buf(self) -> bytes::Bytes {
+        bytes::Bytes::from(self)
+    }
+}
+
+/// Stored as is, without copying. Feature `bytes` must be enabled.
+#[cfg(feature = "bytes")]
+impl Cacheable for bytes::Bytes {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(bytes::Bytes::copy_from_slice(bytes))
+    }
+
+    fn into_buf(self) -> bytes::Bytes {
+        self
+    }
+
+    fn from_buf(buf: bytes::Bytes) -> anyhow::Result<Self> {
+        Ok(buf)
+    }
 }
 
 macro_rules! impl_numeric {
     ($ty: ty) => {
+        impl_numeric!($ty, {});
+    };
+    ($ty: ty, { $($extra: tt)* }) => {
         impl Cacheable for $ty {
             fn to_bytes(&self) -> Vec<u8> {
                 let num = *self as u128;
@@ -91,10 +250,12 @@ macro_rules! impl_numeric {
                 Self: Sized
             {
                 let mut rdr = Cursor::new(bytes);
-                let num = rdr.read_u128::<BigEndian>().unwrap();
+                let num = rdr.read_u128::<BigEndian>()?;
 
                 Ok(num as $ty)
             }
+
+            $($extra)*
         }
     };
     ($($ty: ty),+ $(,)?) => {
@@ -109,10 +270,25 @@ impl_numeric!(
     u64, i64,
     u32, i32,
     u16, i16,
-    u8, i8,
+    i8,
     usize, isize,
 );
 
+impl_numeric!(u8, {
+    fn slice_to_bytes(items: &[Self]) -> Vec<u8> {
+        items.to_vec()
+    }
+
+    fn vec_from_bytes(bytes: &[u8]) -> anyhow::Result<Vec<Self>> {
+        Ok(bytes.to_vec())
+    }
+
+    #[cfg(feature = "bytes")]
+    fn vec_into_buf(items: Vec<Self>) -> bytes::Bytes {
+        bytes::Bytes::from(items)
+    }
+});
+
 impl Cacheable for bool {
     fn to_bytes(&self) -> Vec<u8> {
         if *self {
@@ -130,6 +306,578 @@ impl Cacheable for bool {
     }
 }
 
+impl<T: Cacheable> Cacheable for Box<T> {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_ref().to_bytes()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Box::new(T::from_bytes(bytes)?))
+    }
+
+    #[cfg(feature = "bytes")]
+    fn into_buf(self) -> bytes::Bytes {
+        (*self).into_buf()
+    }
+
+    #[cfg(feature = "bytes")]
+    fn from_buf(buf: bytes::Bytes) -> anyhow::Result<Self> {
+        Ok(Box::new(T::from_buf(buf)?))
+    }
+
+    fn type_name() -> &'static str {
+        T::type_name()
+    }
+
+    fn schema_version() -> u32 {
+        T::schema_version()
+    }
+
+    fn accepts_schema_version(version: u32) -> bool {
+        T::accepts_schema_version(version)
+    }
+}
+
+impl Cacheable for Cow<'_, str> {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Cow::Owned(String::from_utf8(bytes.to_vec())?))
+    }
+}
+
+impl Cacheable for PathBuf {
+    #[cfg(unix)]
+    fn to_bytes(&self) -> Vec<u8> {
+        use std::os::unix::ffi::OsStrExt;
+        self.as_os_str().as_bytes().to_vec()
+    }
+
+    #[cfg(not(unix))]
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_string_lossy().as_bytes().to_vec()
+    }
+
+    #[cfg(unix)]
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)))
+    }
+
+    #[cfg(not(unix))]
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(PathBuf::from(std::str::from_utf8(bytes)?))
+    }
+}
+
+/// Read exactly `N` bytes, used by fixed-size encodings.
+fn fixed<const N: usize>(bytes: &[u8]) -> anyhow::Result<[u8; N]> {
+    bytes.try_into()
+        .map_err(|_| anyhow::anyhow!("expect {} bytes, found {}", N, bytes.len()))
+}
+
+impl Cacheable for f32 {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_bits().to_be_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(f32::from_bits(u32::from_be_bytes(fixed(bytes)?)))
+    }
+}
+
+impl Cacheable for f64 {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_bits().to_be_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(f64::from_bits(u64::from_be_bytes(fixed(bytes)?)))
+    }
+}
+
+impl Cacheable for char {
+    fn to_bytes(&self) -> Vec<u8> {
+        (*self as u32).to_be_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let code = u32::from_be_bytes(fixed(bytes)?);
+        char::from_u32(code)
+            .ok_or_else(|| anyhow::anyhow!("invalid char: {:#x}", code))
+    }
+}
+
+/// Encoded as seconds (`u64`) and nanoseconds (`u32`).
+impl Cacheable for Duration {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut wtr = Vec::with_capacity(12);
+        wtr.write_u64::<BigEndian>(self.as_secs()).unwrap();
+        wtr.write_u32::<BigEndian>(self.subsec_nanos()).unwrap();
+
+        wtr
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let bytes: [u8; 12] = fixed(bytes)?;
+        let mut rdr = Cursor::new(bytes);
+        let secs = rdr.read_u64::<BigEndian>()?;
+        let nanos = rdr.read_u32::<BigEndian>()?;
+        anyhow::ensure!(nanos < 1_000_000_000, "invalid nanoseconds: {}", nanos);
+
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+/// Encoded as seconds (`i64`) and nanoseconds (`u32`) since [`UNIX_EPOCH`].
+/// Times before [`UNIX_EPOCH`] have negative seconds.
+impl Cacheable for SystemTime {
+    fn to_bytes(&self) -> Vec<u8> {
+        let (secs, nanos) = match self.duration_since(UNIX_EPOCH) {
+            Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+            Err(e) => {
+                let d = e.duration();
+                match d.subsec_nanos() {
+                    0 => (-(d.as_secs() as i64), 0),
+                    nanos => (-(d.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+                }
+            },
+        };
+
+        let mut wtr = Vec::with_capacity(12);
+        wtr.write_i64::<BigEndian>(secs).unwrap();
+        wtr.write_u32::<BigEndian>(nanos).unwrap();
+
+        wtr
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let bytes: [u8; 12] = fixed(bytes)?;
+        let mut rdr = Cursor::new(bytes);
+        let secs = rdr.read_i64::<BigEndian>()?;
+        let nanos = rdr.read_u32::<BigEndian>()?;
+        anyhow::ensure!(nanos < 1_000_000_000, "invalid nanoseconds: {}", nanos);
+
+        let time = if secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))
+        } else {
+            UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+                .and_then(|time| time.checked_add(Duration::from_nanos(nanos as u64)))
+        };
+
+        time.ok_or_else(|| anyhow::anyhow!("system time out of range"))
+    }
+}
+
+/// Encoded as `Vec<T>`. For example, a 16-byte id (such as a UUID) is stored as is.
+impl<T: Cacheable, const N: usize> Cacheable for [T; N] {
+    fn to_bytes(&self) -> Vec<u8> {
+        T::slice_to_bytes(self)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let items = T::vec_from_bytes(bytes)?;
+        let len = items.len();
+
+        items.try_into()
+            .map_err(|_| anyhow::anyhow!("expect {} items, found {}", N, len))
+    }
+}
+
+impl Cacheable for Ipv4Addr {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.octets().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Ipv4Addr::from(fixed::<4>(bytes)?))
+    }
+}
+
+impl Cacheable for Ipv6Addr {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.octets().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Ipv6Addr::from(fixed::<16>(bytes)?))
+    }
+}
+
+/// Encoded as a tag (`4` or `6`) followed by the octets.
+impl Cacheable for IpAddr {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut wtr = Vec::with_capacity(17);
+        match self {
+            IpAddr::V4(ip) => {
+                wtr.push(4);
+                wtr.extend_from_slice(&ip.octets());
+            },
+            IpAddr::V6(ip) => {
+                wtr.push(6);
+                wtr.extend_from_slice(&ip.octets());
+            },
+        }
+
+        wtr
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        match bytes.split_first() {
+            Some((4, rest)) => Ok(IpAddr::V4(Ipv4Addr::from_bytes(rest)?)),
+            Some((6, rest)) => Ok(IpAddr::V6(Ipv6Addr::from_bytes(rest)?)),
+            _ => anyhow::bail!("invalid ip address"),
+        }
+    }
+}
+
+/// Encoded as the octets followed by the port.
+impl Cacheable for SocketAddrV4 {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut wtr = Vec::with_capacity(6);
+        wtr.extend_from_slice(&self.ip().octets());
+        wtr.write_u16::<BigEndian>(self.port()).unwrap();
+
+        wtr
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let bytes: [u8; 6] = fixed(bytes)?;
+        let ip = Ipv4Addr::from_bytes(&bytes[..4])?;
+        let port = u16::from_be_bytes([bytes[4], bytes[5]]);
+
+        Ok(SocketAddrV4::new(ip, port))
+    }
+}
+
+/// Encoded as the octets, the port, the flow info and the scope id.
+impl Cacheable for SocketAddrV6 {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut wtr = Vec::with_capacity(26);
+        wtr.extend_from_slice(&self.ip().octets());
+        wtr.write_u16::<BigEndian>(self.port()).unwrap();
+        wtr.write_u32::<BigEndian>(self.flowinfo()).unwrap();
+        wtr.write_u32::<BigEndian>(self.scope_id()).unwrap();
+
+        wtr
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let bytes: [u8; 26] = fixed(bytes)?;
+        let ip = Ipv6Addr::from_bytes(&bytes[..16])?;
+        let mut rdr = Cursor::new(&bytes[16..]);
+        let port = rdr.read_u16::<BigEndian>()?;
+        let flowinfo = rdr.read_u32::<BigEndian>()?;
+        let scope_id = rdr.read_u32::<BigEndian>()?;
+
+        Ok(SocketAddrV6::new(ip, port, flowinfo, scope_id))
+    }
+}
+
+/// Encoded as a tag (`4` or `6`) followed by [`SocketAddrV4`] or [`SocketAddrV6`].
+impl Cacheable for SocketAddr {
+    fn to_bytes(&self) -> Vec<u8> {
+        let (tag, bytes) = match self {
+            SocketAddr::V4(addr) => (4, addr.to_bytes()),
+            SocketAddr::V6(addr) => (6, addr.to_bytes()),
+        };
+
+        let mut wtr = Vec::with_capacity(1 + bytes.len());
+        wtr.push(tag);
+        wtr.extend_from_slice(&bytes);
+
+        wtr
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        match bytes.split_first() {
+            Some((4, rest)) => Ok(SocketAddr::V4(SocketAddrV4::from_bytes(rest)?)),
+            Some((6, rest)) => Ok(SocketAddr::V6(SocketAddrV6::from_bytes(rest)?)),
+            _ => anyhow::bail!("invalid socket address"),
+        }
+    }
+}
+
+/// Encoded as a tag (`0` for `None`, `1` for `Some`) followed by the value.
+impl<T: Cacheable> Cacheable for Option<T> {
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            None => vec![0],
+            Some(value) => {
+                let mut wtr = vec![1];
+                wtr.extend_from_slice(&value.to_bytes());
+                wtr
+            },
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        match bytes.split_first() {
+            Some((0, [])) => Ok(None),
+            Some((1, rest)) => Ok(Some(T::from_bytes(rest)?)),
+            _ => anyhow::bail!("invalid option"),
+        }
+    }
+}
+
+/// Encoded as a tag (`0` for `Ok`, `1` for `Err`) followed by the value.
+impl<T: Cacheable, E: Cacheable> Cacheable for Result<T, E> {
+    fn to_bytes(&self) -> Vec<u8> {
+        let (tag, bytes) = match self {
+            Ok(value) => (0, value.to_bytes()),
+            Err(err) => (1, err.to_bytes()),
+        };
+
+        let mut wtr = Vec::with_capacity(1 + bytes.len());
+        wtr.push(tag);
+        wtr.extend_from_slice(&bytes);
+
+        wtr
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        match bytes.split_first() {
+            Some((0, rest)) => Ok(Ok(T::from_bytes(rest)?)),
+            Some((1, rest)) => Ok(Err(E::from_bytes(rest)?)),
+            _ => anyhow::bail!("invalid result"),
+        }
+    }
+}
+
+/// Each element is length-prefixed.
+macro_rules! impl_tuple {
+    ($($name: ident),+) => {
+        impl<$($name: Cacheable),+> Cacheable for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn to_bytes(&self) -> Vec<u8> {
+                let ($($name,)+) = self;
+                let mut wtr = Writer::default();
+                $(wtr.item($name);)+
+                wtr.finish()
+            }
+
+            fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+            where
+                Self: Sized,
+            {
+                let mut rdr = Reader::new(bytes);
+                let ret = ($(rdr.item::<$name>()?,)+);
+                rdr.finish()?;
+
+                Ok(ret)
+            }
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
+impl_tuple!(A, B, C, D, E, F, G, H, I);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/// Encoded as the number of items followed by length-prefixed items.
+macro_rules! impl_seq {
+    ($ty: ident < T $(: $bound: ident $(+ $bounds: ident)*)? $(, $s: ident: $sbound: ident)? >) => {
+        impl<T: Cacheable $(+ $bound $(+ $bounds)*)? $(, $s: $sbound + Default)?> Cacheable for $ty<T $(, $s)?> {
+            fn to_bytes(&self) -> Vec<u8> {
+                let mut wtr = Writer::with_count(self.len());
+                self.iter().for_each(|item| wtr.item(item));
+                wtr.finish()
+            }
+
+            fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+            where
+                Self: Sized,
+            {
+                let mut rdr = Reader::new(bytes);
+                let ret = (0..rdr.count()?)
+                    .map(|_| rdr.item())
+                    .collect::<anyhow::Result<Self>>()?;
+                rdr.finish()?;
+
+                Ok(ret)
+            }
+        }
+    };
+}
+
+impl_seq!(VecDeque<T>);
+impl_seq!(HashSet<T: Eq + Hash, S: BuildHasher>);
+impl_seq!(BTreeSet<T: Ord>);
+
+/// Encoded as the number of entries followed by length-prefixed keys and values.
+macro_rules! impl_map {
+    ($ty: ident < K: $bound: ident $(+ $bounds: ident)*, V $(, $s: ident: $sbound: ident)? >) => {
+        impl<K: Cacheable + $bound $(+ $bounds)*, V: Cacheable $(, $s: $sbound + Default)?> Cacheable for $ty<K, V $(, $s)?> {
+            fn to_bytes(&self) -> Vec<u8> {
+                let mut wtr = Writer::with_count(self.len());
+                self.iter().for_each(|(key, value)| {
+                    wtr.item(key);
+                    wtr.item(value);
+                });
+                wtr.finish()
+            }
+
+            fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+            where
+                Self: Sized,
+            {
+                let mut rdr = Reader::new(bytes);
+                let ret = (0..rdr.count()?)
+                    .map(|_| Ok((rdr.item()?, rdr.item()?)))
+                    .collect::<anyhow::Result<Self>>()?;
+                rdr.finish()?;
+
+                Ok(ret)
+            }
+        }
+    };
+}
+
+impl_map!(HashMap<K: Eq + Hash, V, S: BuildHasher>);
+impl_map!(BTreeMap<K: Ord, V>);
+
+/// Writes length-prefixed items.
+///
+/// Counts and lengths are written as `u32`.
+#[derive(Default)]
+pub(crate) struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    /// # Panics
+    ///
+    /// Panics if `count` doesn't fit in a `u32`.
+    pub(crate) fn with_count(count: usize) -> Self {
+        let count = u32::try_from(count).expect("too many items to be cached");
+        let mut wtr = Self::default();
+        wtr.buf.write_u32::<BigEndian>(count).unwrap();
+        wtr
+    }
+
+    pub(crate) fn item<T: Cacheable>(&mut self, item: &T) {
+        self.raw(&item.to_bytes());
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `bytes` is 4 GiB or larger.
+    pub(crate) fn raw(&mut self, bytes: &[u8]) {
+        let len = u32::try_from(bytes.len()).expect("item is too large to be cached");
+        self.buf.write_u32::<BigEndian>(len).unwrap();
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Reads items written by [`Writer`].
+pub(crate) struct Reader<'a> {
+    rdr: Cursor<&'a [u8]>,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            rdr: Cursor::new(bytes),
+        }
+    }
+
+    pub(crate) fn count(&mut self) -> anyhow::Result<usize> {
+        Ok(self.rdr.read_u32::<BigEndian>()? as usize)
+    }
+
+    pub(crate) fn item<T: Cacheable>(&mut self) -> anyhow::Result<T> {
+        T::from_bytes(self.raw()?)
+    }
+
+    pub(crate) fn raw(&mut self) -> anyhow::Result<&'a [u8]> {
+        let len = self.rdr.read_u32::<BigEndian>()? as usize;
+        let start = self.rdr.position() as usize;
+        let bytes = *self.rdr.get_ref();
+        let item = bytes.get(start..start + len)
+            .ok_or_else(|| anyhow::anyhow!("unexpected end of bytes"))?;
+        self.rdr.set_position((start + len) as u64);
+
+        Ok(item)
+    }
+
+    /// Ensure that all bytes are consumed.
+    pub(crate) fn finish(self) -> anyhow::Result<()> {
+        let remaining = self.rdr.get_ref().len() - self.rdr.position() as usize;
+        anyhow::ensure!(remaining == 0, "unexpected {} trailing bytes", remaining);
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -221,4 +969,207 @@ mod tests {
 
         Ok(())
     }
+
+    fn round_trip<T: Cacheable + PartialEq>(value: T) {
+        let v = value.to_bytes();
+        let d: T = Cacheable::from_bytes(&v).unwrap();
+        assert_eq!(value, d);
+    }
+
+    #[test]
+    fn test_float() -> anyhow::Result<()> {
+        for _ in 0..1024 {
+            let bits: u32 = random();
+            let d = f32::from_bytes(&f32::from_bits(bits).to_bytes())?;
+            assert_eq!(bits, d.to_bits());
+
+            let bits: u64 = random();
+            let d = f64::from_bytes(&f64::from_bits(bits).to_bytes())?;
+            assert_eq!(bits, d.to_bits());
+        }
+
+        for f in [0.0, -0.0, f64::INFINITY, f64::NEG_INFINITY, f64::MIN_POSITIVE, f64::NAN] {
+            let d = f64::from_bytes(&f.to_bytes())?;
+            assert_eq!(f.to_bits(), d.to_bits());
+        }
+
+        assert!(f32::from_bytes(&[0, 0]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_char() -> anyhow::Result<()> {
+        for _ in 0..1024 {
+            round_trip::<char>(random());
+        }
+        round_trip('\u{10ffff}');
+
+        assert!(char::from_bytes(&0xd800u32.to_be_bytes()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time() -> anyhow::Result<()> {
+        for _ in 0..1024 {
+            let d = Duration::new(random(), thread_rng().gen_range(0..1_000_000_000));
+            round_trip(d);
+
+            let d = Duration::new(thread_rng().gen_range(0..1 << 40), thread_rng().gen_range(0..1_000_000_000));
+            round_trip(UNIX_EPOCH + d);
+            round_trip(UNIX_EPOCH - d);
+        }
+
+        round_trip(Duration::MAX);
+        round_trip(SystemTime::now());
+        round_trip(UNIX_EPOCH);
+        round_trip(UNIX_EPOCH - Duration::from_nanos(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_network() -> anyhow::Result<()> {
+        for _ in 0..1024 {
+            let v4 = Ipv4Addr::from(random::<u32>());
+            let v6 = Ipv6Addr::from(random::<u128>());
+
+            round_trip(v4);
+            round_trip(v6);
+            round_trip(IpAddr::V4(v4));
+            round_trip(IpAddr::V6(v6));
+            round_trip(SocketAddr::V4(SocketAddrV4::new(v4, random())));
+            round_trip(SocketAddr::V6(SocketAddrV6::new(v6, random(), random(), random())));
+        }
+
+        assert!(IpAddr::from_bytes(&[5, 1, 2, 3, 4]).is_err());
+        assert!(SocketAddr::from_bytes(&[]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_misc() -> anyhow::Result<()> {
+        for _ in 0..1024 {
+            round_trip::<[u8; 16]>(random());
+            round_trip(Box::new(random::<u64>()));
+        }
+        assert!(<[u8; 16]>::from_bytes(&[0; 15]).is_err());
+
+        round_trip(Cow::Borrowed("hello"));
+        round_trip::<Cow<str>>(Cow::Owned(String::from("world")));
+        round_trip(PathBuf::from("/tmp/cache-any/a.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_option_and_result() -> anyhow::Result<()> {
+        round_trip::<Option<u64>>(None);
+        round_trip(Some(random::<u64>()));
+        round_trip(Some(String::from("profile")));
+        round_trip(Some(None::<u8>));
+        round_trip(Some(Some(())));
+
+        round_trip::<Result<u32, String>>(Ok(random()));
+        round_trip::<Result<u32, String>>(Err(String::from("not found")));
+
+        assert!(Option::<u8>::from_bytes(&[]).is_err());
+        assert!(Option::<u8>::from_bytes(&[0, 1]).is_err());
+        assert!(Result::<u8, u8>::from_bytes(&[2]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tuple() -> anyhow::Result<()> {
+        round_trip((random::<u8>(),));
+        round_trip((random::<u64>(), String::from("a"), vec![1u8, 2, 3]));
+        round_trip((1u8, 2u16, 3u32, 4u64, 5u128, 6i8, 7i16, 8i32, 9i64, 10i128, String::from("11"), true));
+
+        let bytes = (1u8, 2u8).to_bytes();
+        assert!(<(u8, u8, u8)>::from_bytes(&bytes).is_err());
+        assert!(<(u8,)>::from_bytes(&bytes).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collections() -> anyhow::Result<()> {
+        let ids: Vec<u64> = (0..128).map(|_| random()).collect();
+        round_trip(ids.clone());
+        round_trip(Vec::<u64>::new());
+        round_trip(vec![Some(String::from("a")), None, Some(String::new())]);
+        round_trip(vec![vec![1u8, 2], vec![], vec![3]]);
+        round_trip(ids.iter().copied().collect::<VecDeque<_>>());
+        round_trip(ids.iter().copied().collect::<HashSet<_>>());
+        round_trip(ids.iter().copied().collect::<BTreeSet<_>>());
+        round_trip(ids.iter().map(|id| (*id, id.to_string())).collect::<HashMap<_, _>>());
+        round_trip(ids.iter().map(|id| (id.to_string(), *id)).collect::<BTreeMap<_, _>>());
+
+        let array: [u64; 4] = random();
+        round_trip(array);
+        assert!(<[u64; 3]>::from_bytes(&array.to_bytes()).is_err());
+
+        // `Vec<u8>` keeps its raw-bytes meaning.
+        let bytes = vec![0u8, 1, 2, 4];
+        assert_eq!(bytes.to_bytes(), bytes);
+        assert_eq!([7u8; 4].to_bytes(), vec![7u8; 4]);
+
+        let mut bytes = ids.to_bytes();
+        bytes.push(0);
+        assert!(Vec::<u64>::from_bytes(&bytes).is_err());
+        assert!(Vec::<u64>::from_bytes(&bytes[..bytes.len() - 2]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated() -> anyhow::Result<()> {
+        assert!(u64::from_bytes(&[0, 0, 0]).is_err());
+        assert!(Option::<u64>::from_bytes(&[1]).is_err());
+        assert!(Result::<u64, String>::from_bytes(&[0, 1]).is_err());
+
+        let bytes = (1u64, String::from("a")).to_bytes();
+        assert!(<(u64, String)>::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // items are length-prefixed, so a short item is detected inside a complete collection.
+        let mut wtr = Writer::with_count(1);
+        wtr.raw(&[0, 1]);
+        assert!(Vec::<u64>::from_bytes(&wtr.finish()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    #[should_panic(expected = "too many items to be cached")]
+    fn test_writer_too_many_items() {
+        Writer::with_count(u32::MAX as usize + 1);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_bytes() -> anyhow::Result<()> {
+        let buf = bytes::Bytes::from(vec![0u8, 1, 2, 4]);
+        round_trip(buf.clone());
+
+        let ptr = buf.as_ptr();
+        let d = bytes::Bytes::from_buf(buf.into_buf())?;
+        assert_eq!(d.as_ptr(), ptr);
+
+        let v = vec![0u8, 1, 2, 4];
+        let ptr = v.as_ptr();
+        assert_eq!(v.into_buf().as_ptr(), ptr);
+
+        let s = String::from("hello");
+        let ptr = s.as_ptr();
+        assert_eq!(s.into_buf().as_ptr(), ptr);
+
+        assert_eq!(vec![1u64, 2].into_buf(), vec![1u64, 2].to_bytes());
+        assert_eq!(u64::from_buf(7u64.into_buf())?, 7);
+
+        Ok(())
+    }
 }
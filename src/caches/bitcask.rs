@@ -0,0 +1,1029 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::{Cache, CacheKey, Cacheable};
+
+/// crc, expiration, key length and value length.
+const RECORD_HEADER_LEN: usize = 4 + 8 + 4 + 4;
+/// The value length of a tombstone, which records a deletion.
+const TOMBSTONE: u32 = u32::MAX;
+
+/// expiration, offset, record length and key length.
+const HINT_HEADER_LEN: usize = 8 + 8 + 4 + 4;
+
+const DATA_EXTENSION: &str = "data";
+const HINT_EXTENSION: &str = "hint";
+
+/// [`BitcaskCache`] is a persistent cache storing data in append-only log files,
+/// following the design of [Bitcask](https://riak.com/assets/bitcask-intro.pdf).
+///
+/// Feature `bitcask` must be enabled.
+///
+/// It needs no external service, and writes are fast as they only append to a file.
+/// The directory must be used by a single process.
+///
+/// ## Design
+///
+/// * Every `set` and `delete` appends a CRC-checked record to the active data file.
+///   When it exceeds [`BitcaskCacheBuilder::max_file_size`], a new active file is created.
+/// * The location of the latest record of every key is kept in memory (the key directory),
+///   so a `get` reads the disk only once. Keys must fit in memory, values don't have to.
+///   A key and its value must be smaller than 4 GiB together.
+/// * On startup, the key directory is rebuilt from the hint files, or by scanning the data files.
+///   A record torn by a crash is detected by its CRC, and the file is truncated before it.
+/// * Compaction rewrites the live records of inactive files into new files with hint files,
+///   and removes the old ones. Run it by [`BitcaskCache::compact`], or in the background
+///   with [`BitcaskCacheBuilder::compaction_interval`]. Other operations only wait while
+///   it takes a snapshot of the key directory, and while it switches to the new files.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache = BitcaskCacheBuilder::new("/var/lib/app/cache")
+///     .max_file_size(64 * 1024 * 1024)
+///     .compaction_interval(Duration::from_secs(600))
+///     .finish()
+///     .await?;
+///
+/// cache.set("a", 1).await?;
+/// let a: Option<u8> = cache.get("a").await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct BitcaskCache {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    dir: PathBuf,
+    max_file_size: u64,
+    sync: bool,
+    state: Mutex<State>,
+    /// Held while a compaction runs.
+    compaction: Mutex<()>,
+}
+
+/// Data files are ordered by ids. Files written by compaction have a positive `minor`,
+/// so that they are ordered after the files they replace, and before newer files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct FileId {
+    major: u64,
+    minor: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Location {
+    file: FileId,
+    offset: u64,
+    len: u32,
+    /// Milliseconds since the unix epoch, 0 if it never expires.
+    expires_at: i64,
+}
+
+impl Location {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at != 0 && self.expires_at <= now
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    keydir: HashMap<Vec<u8>, Location>,
+    /// Read handles of all data files, including the active one.
+    files: BTreeMap<FileId, File>,
+    active: FileId,
+    active_file: File,
+    active_len: u64,
+    /// The total size of data files.
+    total: u64,
+    /// The size of records which are overwritten, deleted, or tombstones.
+    stale: u64,
+}
+
+/// A record decoded from a data file.
+struct Record {
+    expires_at: i64,
+    key: Vec<u8>,
+    /// `None` for a tombstone.
+    value: Option<Vec<u8>>,
+}
+
+impl Record {
+    fn encode(key: &[u8], value: Option<&[u8]>, expires_at: i64) -> Vec<u8> {
+        let value_len = value.map_or(TOMBSTONE, |value| value.len() as u32);
+        let value = value.unwrap_or_default();
+
+        let mut ret = Vec::with_capacity(RECORD_HEADER_LEN + key.len() + value.len());
+        ret.extend_from_slice(&[0; 4]);
+        ret.extend_from_slice(&expires_at.to_be_bytes());
+        ret.extend_from_slice(&(key.len() as u32).to_be_bytes());
+        ret.extend_from_slice(&value_len.to_be_bytes());
+        ret.extend_from_slice(key);
+        ret.extend_from_slice(value);
+
+        let crc = crc32c::crc32c(&ret[4..]);
+        ret[..4].copy_from_slice(&crc.to_be_bytes());
+        ret
+    }
+
+    /// Read the next record within `remaining` bytes, and return it with its length.
+    /// Returns `None` at the end of the file, or if the record is torn or corrupted.
+    fn read_from<R: Read>(reader: &mut R, remaining: u64) -> std::io::Result<Option<(Self, u32)>> {
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        if !read_exact_or_eof(reader, &mut header)? {
+            return Ok(None);
+        }
+
+        let crc = u32::from_be_bytes(header[..4].try_into().unwrap());
+        let expires_at = i64::from_be_bytes(header[4..12].try_into().unwrap());
+        let key_len = u32::from_be_bytes(header[12..16].try_into().unwrap()) as usize;
+        let value_len = u32::from_be_bytes(header[16..20].try_into().unwrap());
+        let body_len = key_len + if value_len == TOMBSTONE { 0 } else { value_len as usize };
+        // lengths beyond the file are corrupted, so they are not allocated.
+        if (RECORD_HEADER_LEN + body_len) as u64 > remaining {
+            return Ok(None);
+        }
+
+        let mut body = vec![0u8; body_len];
+        if !read_exact_or_eof(reader, &mut body)? {
+            return Ok(None);
+        }
+
+        let actual = crc32c::crc32c_append(crc32c::crc32c(&header[4..]), &body);
+        if actual != crc {
+            return Ok(None);
+        }
+
+        let value = (value_len != TOMBSTONE).then(|| body.split_off(key_len));
+        let record = Self { expires_at, key: body, value };
+        Ok(Some((record, (RECORD_HEADER_LEN + body_len) as u32)))
+    }
+}
+
+impl BitcaskCache {
+    /// Get the directory of data files.
+    pub fn dir(&self) -> &Path {
+        &self.inner.dir
+    }
+
+    /// Rewrite the live records of inactive files, and remove stale records.
+    pub async fn compact(&self) -> anyhow::Result<()> {
+        let inner = self.inner.clone();
+        blocking(move || inner.compact()).await
+    }
+
+    async fn write(&self, key: Vec<u8>, value: Option<Vec<u8>>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        let inner = self.inner.clone();
+        blocking(move || inner.write(key, value.as_deref(), ttl)).await
+    }
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn path(&self, id: FileId, extension: &str) -> PathBuf {
+        file_path(&self.dir, id, extension)
+    }
+
+    fn read(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut state = self.lock();
+        let Some(location) = state.keydir.get(key).copied() else {
+            return Ok(None);
+        };
+        if location.is_expired(unix_millis(SystemTime::now())) {
+            return Ok(None);
+        }
+
+        let file = state.files.get_mut(&location.file)
+            .ok_or_else(|| anyhow::anyhow!("data file {:?} is missing", location.file))?;
+        file.seek(SeekFrom::Start(location.offset))?;
+
+        let mut reader = file.take(location.len as u64);
+        match Record::read_from(&mut reader, location.len as u64)? {
+            Some((record, _)) if record.key == key => Ok(record.value),
+            _ => anyhow::bail!("corrupted record at {:?}:{}", location.file, location.offset),
+        }
+    }
+
+    fn write(&self, key: Vec<u8>, value: Option<&[u8]>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        let mut state = self.lock();
+        if value.is_none() && !state.keydir.contains_key(&key) {
+            return Ok(());
+        }
+
+        check_record_len(key.len(), value.map_or(0, |value| value.len()))?;
+        let expires_at = ttl.map(expires_at).unwrap_or_default();
+        let record = Record::encode(&key, value, expires_at);
+
+        if state.active_len > 0 && state.active_len + record.len() as u64 > self.max_file_size {
+            self.roll(&mut state)?;
+        }
+
+        let offset = state.active_len;
+        state.active_file.write_all(&record)?;
+        if self.sync {
+            state.active_file.sync_data()?;
+        }
+        state.active_len += record.len() as u64;
+        state.total += record.len() as u64;
+
+        let location = Location {
+            file: state.active,
+            offset,
+            len: record.len() as u32,
+            expires_at,
+        };
+
+        let replaced = match value {
+            Some(_) => state.keydir.insert(key, location),
+            None => {
+                state.stale += record.len() as u64;
+                state.keydir.remove(&key)
+            },
+        };
+        if let Some(replaced) = replaced {
+            state.stale += replaced.len as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Create a new active file.
+    fn roll(&self, state: &mut State) -> anyhow::Result<()> {
+        let id = FileId { major: state.active.major + 1, minor: 0 };
+        let (file, read_file) = self.create(id)?;
+
+        state.active = id;
+        state.active_file = file;
+        state.active_len = 0;
+        state.files.insert(id, read_file);
+
+        Ok(())
+    }
+
+    /// Create a data file, and return its append and read handles.
+    fn create(&self, id: FileId) -> anyhow::Result<(File, File)> {
+        let path = self.path(id, DATA_EXTENSION);
+        let file = OpenOptions::new().create_new(true).append(true).open(&path)?;
+        let read_file = File::open(&path)?;
+        Ok((file, read_file))
+    }
+
+    fn compact(&self) -> anyhow::Result<()> {
+        // one compaction at a time, as they would write the same file ids.
+        let _compacting = self.compaction.lock().unwrap_or_else(|err| err.into_inner());
+
+        let Some(compaction) = self.prepare_compaction()? else {
+            return Ok(());
+        };
+        let merged = self.merge(compaction)?;
+        self.install(merged)
+    }
+
+    /// Roll the active file, and take a snapshot of the live records in the inactive files.
+    fn prepare_compaction(&self) -> anyhow::Result<Option<Compaction>> {
+        let mut state = self.lock();
+        self.roll(&mut state)?;
+
+        let inactive: Vec<FileId> = state.files.keys()
+            .copied()
+            .filter(|id| *id < state.active)
+            .collect();
+        let Some(&last) = inactive.last() else {
+            return Ok(None);
+        };
+
+        // ordered after all inactive files, and before the active one.
+        let major = last.major;
+        let minor = inactive.iter()
+            .filter(|id| id.major == major)
+            .map(|id| id.minor)
+            .max()
+            .unwrap_or_default();
+
+        let mut live: Vec<(Vec<u8>, Location)> = state.keydir.iter()
+            .filter(|(_, location)| location.file < state.active)
+            .map(|(key, location)| (key.clone(), *location))
+            .collect();
+        live.sort_by_key(|(_, location)| (location.file, location.offset));
+
+        // inactive files are immutable. They are opened again, as the handles in the state are
+        // seeked by reads.
+        let mut files = HashMap::with_capacity(inactive.len());
+        let mut inactive_len = 0;
+        for &id in &inactive {
+            let file = File::open(self.path(id, DATA_EXTENSION))?;
+            inactive_len += file.metadata()?.len();
+            files.insert(id, file);
+        }
+
+        Ok(Some(Compaction {
+            inactive,
+            inactive_len,
+            files,
+            live,
+            major,
+            minor,
+            stale: state.stale,
+        }))
+    }
+
+    /// Rewrite the live records of a snapshot into new files, without locking the state.
+    fn merge(&self, mut compaction: Compaction) -> anyhow::Result<Merged> {
+        let now = unix_millis(SystemTime::now());
+        let major = compaction.major;
+        let mut minor = compaction.minor;
+
+        let mut outputs = Vec::new();
+        let mut output: Option<Output> = None;
+        let mut moved = Vec::with_capacity(compaction.live.len());
+        for (key, location) in std::mem::take(&mut compaction.live) {
+            if location.is_expired(now) {
+                moved.push((key, location, None));
+                continue;
+            }
+
+            let mut record = vec![0u8; location.len as usize];
+            let file = compaction.files.get_mut(&location.file).unwrap();
+            file.seek(SeekFrom::Start(location.offset))?;
+            file.read_exact(&mut record)?;
+
+            let full = match &output {
+                None => true,
+                Some(output) => output.len > 0 && output.len + record.len() as u64 > self.max_file_size,
+            };
+            if full {
+                if let Some(output) = output.take() {
+                    outputs.push(output.finish()?);
+                }
+                minor += 1;
+                output = Some(Output::create(self, FileId { major, minor })?);
+            }
+
+            let output = output.as_mut().unwrap();
+            let new_location = Location {
+                file: output.id,
+                offset: output.len,
+                len: location.len,
+                expires_at: location.expires_at,
+            };
+            output.append(&key, &record, new_location)?;
+            moved.push((key, location, Some(new_location)));
+        }
+        if let Some(output) = output.take() {
+            outputs.push(output.finish()?);
+        }
+
+        Ok(Merged { compaction, outputs, moved })
+    }
+
+    /// Point the key directory to the merged files, and remove the inactive files.
+    fn install(&self, merged: Merged) -> anyhow::Result<()> {
+        let Merged { compaction, outputs, moved } = merged;
+        let mut state = self.lock();
+
+        // the merged files are complete, so the inactive files can be removed.
+        // keys written or deleted during the merge keep their new locations.
+        for (key, location, new_location) in moved {
+            if state.keydir.get(&key) != Some(&location) {
+                continue;
+            }
+            match new_location {
+                Some(new_location) => state.keydir.insert(key, new_location),
+                None => state.keydir.remove(&key),
+            };
+        }
+        for id in &compaction.inactive {
+            state.files.remove(id);
+            remove(&self.path(*id, DATA_EXTENSION))?;
+            remove(&self.path(*id, HINT_EXTENSION))?;
+        }
+
+        state.total -= compaction.inactive_len;
+        for (id, len) in outputs {
+            state.files.insert(id, File::open(self.path(id, DATA_EXTENSION))?);
+            state.total += len;
+        }
+        // records which became stale during the merge, in the merged or the newer files.
+        state.stale = state.stale.saturating_sub(compaction.stale);
+
+        Ok(())
+    }
+
+    fn should_compact(&self, threshold: f64) -> bool {
+        let state = self.lock();
+        state.total > 0 && state.stale as f64 / state.total as f64 >= threshold
+    }
+}
+
+/// A snapshot of the inactive files to compact.
+struct Compaction {
+    inactive: Vec<FileId>,
+    /// The total size of the inactive files.
+    inactive_len: u64,
+    files: HashMap<FileId, File>,
+    /// The live records in the inactive files, ordered by their locations.
+    live: Vec<(Vec<u8>, Location)>,
+    /// The id of the last merged file, which the merged files are numbered after.
+    major: u64,
+    minor: u32,
+    /// The size of stale records when the snapshot was taken.
+    stale: u64,
+}
+
+/// The result of a merge: for every live record, the old location and the new one
+/// (`None` if it's expired).
+struct Merged {
+    compaction: Compaction,
+    outputs: Vec<(FileId, u64)>,
+    moved: Vec<(Vec<u8>, Location, Option<Location>)>,
+}
+
+/// A data file and its hint file, written by compaction.
+struct Output {
+    id: FileId,
+    data: BufWriter<File>,
+    hint: BufWriter<File>,
+    hint_crc: u32,
+    len: u64,
+}
+
+impl Output {
+    fn create(inner: &Inner, id: FileId) -> anyhow::Result<Self> {
+        let data = OpenOptions::new().create(true).truncate(true).write(true).open(inner.path(id, DATA_EXTENSION))?;
+        let hint = OpenOptions::new().create(true).truncate(true).write(true).open(inner.path(id, HINT_EXTENSION))?;
+
+        Ok(Self {
+            id,
+            data: BufWriter::new(data),
+            hint: BufWriter::new(hint),
+            hint_crc: 0,
+            len: 0,
+        })
+    }
+
+    fn append(&mut self, key: &[u8], record: &[u8], location: Location) -> anyhow::Result<()> {
+        self.data.write_all(record)?;
+        self.len += record.len() as u64;
+
+        let mut hint = Vec::with_capacity(HINT_HEADER_LEN + key.len());
+        hint.extend_from_slice(&location.expires_at.to_be_bytes());
+        hint.extend_from_slice(&location.offset.to_be_bytes());
+        hint.extend_from_slice(&location.len.to_be_bytes());
+        hint.extend_from_slice(&(key.len() as u32).to_be_bytes());
+        hint.extend_from_slice(key);
+        self.hint_crc = crc32c::crc32c_append(self.hint_crc, &hint);
+        self.hint.write_all(&hint)?;
+
+        Ok(())
+    }
+
+    /// Flush and sync both files. The hint file ends with the CRC of its entries.
+    fn finish(mut self) -> anyhow::Result<(FileId, u64)> {
+        self.data.flush()?;
+        self.data.get_ref().sync_all()?;
+
+        self.hint.write_all(&self.hint_crc.to_be_bytes())?;
+        self.hint.flush()?;
+        self.hint.get_ref().sync_all()?;
+
+        Ok((self.id, self.len))
+    }
+}
+
+/// A key and the location of its record, stored in a hint file.
+type HintEntry = (Vec<u8>, Location);
+
+/// Load the entries of a hint file. Returns `None` if it's missing or invalid.
+fn load_hint(path: &Path, id: FileId) -> anyhow::Result<Option<Vec<HintEntry>>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let Some((entries, crc)) = bytes.len().checked_sub(4).map(|len| bytes.split_at(len)) else {
+        return Ok(None);
+    };
+    if crc32c::crc32c(entries) != u32::from_be_bytes(crc.try_into().unwrap()) {
+        return Ok(None);
+    }
+
+    let mut ret = Vec::new();
+    let mut rest = entries;
+    while !rest.is_empty() {
+        if rest.len() < HINT_HEADER_LEN {
+            return Ok(None);
+        }
+
+        let expires_at = i64::from_be_bytes(rest[..8].try_into().unwrap());
+        let offset = u64::from_be_bytes(rest[8..16].try_into().unwrap());
+        let len = u32::from_be_bytes(rest[16..20].try_into().unwrap());
+        let key_len = u32::from_be_bytes(rest[20..24].try_into().unwrap()) as usize;
+        let Some(key) = rest.get(HINT_HEADER_LEN..HINT_HEADER_LEN + key_len) else {
+            return Ok(None);
+        };
+
+        ret.push((key.to_vec(), Location { file: id, offset, len, expires_at }));
+        rest = &rest[HINT_HEADER_LEN + key_len..];
+    }
+
+    Ok(Some(ret))
+}
+
+/// Scan a data file, and apply its records to the key directory.
+/// The file is truncated before the first torn or corrupted record.
+fn load_data(path: &Path, id: FileId, state: &mut State) -> anyhow::Result<u64> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(&file);
+
+    let mut offset = 0u64;
+    while let Some((record, len)) = Record::read_from(&mut reader, file_len - offset)? {
+        let replaced = match record.value {
+            Some(_) => {
+                let location = Location { file: id, offset, len, expires_at: record.expires_at };
+                state.keydir.insert(record.key, location)
+            },
+            None => {
+                state.stale += len as u64;
+                state.keydir.remove(&record.key)
+            },
+        };
+        if let Some(replaced) = replaced {
+            state.stale += replaced.len as u64;
+        }
+
+        offset += len as u64;
+    }
+
+    if offset < file_len {
+        file.set_len(offset)?;
+        file.sync_all()?;
+    }
+
+    Ok(offset)
+}
+
+fn file_path(dir: &Path, id: FileId, extension: &str) -> PathBuf {
+    dir.join(format!("{:016x}-{:08x}.{}", id.major, id.minor, extension))
+}
+
+fn parse_file_name(path: &Path) -> Option<(FileId, String)> {
+    let extension = path.extension()?.to_str()?.to_string();
+    let (major, minor) = path.file_stem()?.to_str()?.split_once('-')?;
+    let id = FileId {
+        major: u64::from_str_radix(major, 16).ok()?,
+        minor: u32::from_str_radix(minor, 16).ok()?,
+    };
+    Some((id, extension))
+}
+
+fn read_exact_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn remove(path: &Path) -> anyhow::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Records are addressed by `u32` lengths, and a value length of [`TOMBSTONE`] marks a deletion.
+fn check_record_len(key_len: usize, value_len: usize) -> anyhow::Result<()> {
+    let record_len = RECORD_HEADER_LEN as u64 + key_len as u64 + value_len as u64;
+    if record_len > u32::MAX as u64 {
+        anyhow::bail!("the record is too large: {} bytes, at most {} bytes", record_len, u32::MAX);
+    }
+    Ok(())
+}
+
+/// The expiration time after `ttl`, or 0 (never expires) if it overflows.
+fn expires_at(ttl: Duration) -> i64 {
+    i64::try_from(ttl.as_millis()).ok()
+        .and_then(|ttl| unix_millis(SystemTime::now()).checked_add(ttl))
+        .map_or(0, |expires_at| expires_at.max(1))
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+async fn blocking<T, F>(f: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await?
+}
+
+#[async_trait::async_trait]
+impl Cache for BitcaskCache {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let key = key.to_key();
+        let inner = self.inner.clone();
+        let value = blocking(move || inner.read(&key)).await?;
+
+        value.map(|value| T::from_bytes(&value))
+            .transpose()
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        self.write(key.to_key(), Some(value.to_bytes()), None).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        self.write(key.to_key(), None, None).await
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        let inner = self.inner.clone();
+        blocking(move || {
+            let now = unix_millis(SystemTime::now());
+            let state = inner.lock();
+
+            Ok(state.keydir.values().filter(|location| !location.is_expired(now)).count())
+        }).await
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        self.write(key.to_key(), Some(value.to_bytes()), Some(ttl)).await
+    }
+}
+
+/// [`BitcaskCacheBuilder`] is used to build a [`BitcaskCache`].
+#[derive(Debug, Clone)]
+pub struct BitcaskCacheBuilder {
+    dir: PathBuf,
+    max_file_size: u64,
+    sync: bool,
+    compaction_interval: Option<Duration>,
+    compaction_threshold: f64,
+}
+
+impl BitcaskCacheBuilder {
+    /// Create a new [`BitcaskCacheBuilder`]. You need to specify the directory of data files.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            max_file_size: 64 * 1024 * 1024,
+            sync: false,
+            compaction_interval: None,
+            compaction_threshold: 0.5,
+        }
+    }
+
+    /// Set the size of data files, after which a new file is created. It's 64 MiB by default.
+    pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Sync the active file to disk after every write. It's `false` by default,
+    /// so the latest writes may be lost (but never corrupted) on a power failure.
+    pub fn sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Check for compaction periodically in the background. It's disabled by default.
+    ///
+    /// The background task stops when all clones of the cache are dropped.
+    pub fn compaction_interval(mut self, interval: Duration) -> Self {
+        self.compaction_interval = Some(interval);
+        self
+    }
+
+    /// Set the ratio of stale records in data files, above which the background compaction runs.
+    /// It's `0.5` by default.
+    pub fn compaction_threshold(mut self, threshold: f64) -> Self {
+        self.compaction_threshold = threshold;
+        self
+    }
+
+    /// Finish and build a [`BitcaskCache`]. Existing data files in the directory are loaded.
+    ///
+    /// It must be called within a tokio runtime.
+    pub async fn finish(self) -> anyhow::Result<BitcaskCache> {
+        let dir = self.dir.clone();
+        let max_file_size = self.max_file_size;
+        let sync = self.sync;
+        let inner = blocking(move || open(dir, max_file_size, sync)).await?;
+        let inner = Arc::new(inner);
+
+        if let Some(interval) = self.compaction_interval {
+            tokio::spawn(compact_periodically(Arc::downgrade(&inner), interval, self.compaction_threshold));
+        }
+
+        Ok(BitcaskCache { inner })
+    }
+}
+
+fn open(dir: PathBuf, max_file_size: u64, sync: bool) -> anyhow::Result<Inner> {
+    std::fs::create_dir_all(&dir)?;
+
+    let mut ids = BTreeMap::new();
+    for item in std::fs::read_dir(&dir)? {
+        let path = item?.path();
+        if let Some((id, extension)) = parse_file_name(&path) {
+            if extension == DATA_EXTENSION {
+                ids.insert(id, path);
+            }
+        }
+    }
+
+    // a new active file is created on every start.
+    let last = ids.keys().next_back().copied();
+    let active = FileId { major: last.map_or(0, |id| id.major + 1), minor: 0 };
+    let active_path = file_path(&dir, active, DATA_EXTENSION);
+
+    let mut state = State {
+        keydir: HashMap::new(),
+        files: BTreeMap::new(),
+        active,
+        active_file: OpenOptions::new().create(true).append(true).open(&active_path)?,
+        active_len: 0,
+        total: 0,
+        stale: 0,
+    };
+
+    for (id, path) in ids {
+        let len = match load_hint(&file_path(&dir, id, HINT_EXTENSION), id)? {
+            Some(entries) => {
+                for (key, location) in entries {
+                    if let Some(replaced) = state.keydir.insert(key, location) {
+                        state.stale += replaced.len as u64;
+                    }
+                }
+                std::fs::metadata(&path)?.len()
+            },
+            None => load_data(&path, id, &mut state)?,
+        };
+
+        state.total += len;
+        state.files.insert(id, File::open(&path)?);
+    }
+    state.files.insert(active, File::open(&active_path)?);
+
+    Ok(Inner {
+        dir,
+        max_file_size,
+        sync,
+        state: Mutex::new(state),
+        compaction: Mutex::new(()),
+    })
+}
+
+async fn compact_periodically(inner: Weak<Inner>, interval: Duration, threshold: f64) {
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        if inner.should_compact(threshold) {
+            // a failed compaction leaves the data intact, and is retried later.
+            let _ = blocking(move || inner.compact()).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cache-any-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn data_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir).unwrap()
+            .map(|item| item.unwrap().path())
+            .filter(|path| path.extension().unwrap() == DATA_EXTENSION)
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[tokio::test]
+    async fn test_bitcask_cache() -> anyhow::Result<()> {
+        let dir = temp_dir("bitcask");
+        let cache = BitcaskCacheBuilder::new(&dir).finish().await?;
+
+        cache.set("a", 1).await?;
+        cache.set("b", String::from("bbb")).await?;
+        cache.set(b"\xff\x00".as_slice(), vec![0u8, 1, 2, 255]).await?;
+        cache.set("b", String::from("overwritten")).await?;
+        cache.set_with_ttl("c", 3, Duration::from_millis(10)).await?;
+        cache.delete("a").await?;
+        cache.delete("none").await?;
+
+        assert_eq!(cache.get::<u8>("a").await?, None);
+        assert_eq!(cache.get::<String>("b").await?, Some(String::from("overwritten")));
+        assert_eq!(cache.get::<Vec<u8>>(b"\xff\x00".as_slice()).await?, Some(vec![0u8, 1, 2, 255]));
+        assert_eq!(cache.get::<u8>("c").await?, Some(3));
+        assert_eq!(cache.len().await?, 3);
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.get::<u8>("c").await?, None);
+        assert_eq!(cache.len().await?, 2);
+
+        // reloaded by scanning the data file.
+        drop(cache);
+        let cache = BitcaskCacheBuilder::new(&dir).finish().await?;
+        assert_eq!(cache.get::<u8>("a").await?, None);
+        assert_eq!(cache.get::<String>("b").await?, Some(String::from("overwritten")));
+        assert_eq!(cache.get::<Vec<u8>>(b"\xff\x00".as_slice()).await?, Some(vec![0u8, 1, 2, 255]));
+        assert_eq!(cache.len().await?, 2);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitcask_cache_recovery() -> anyhow::Result<()> {
+        let dir = temp_dir("bitcask-recovery");
+        let cache = BitcaskCacheBuilder::new(&dir).finish().await?;
+        cache.set("a", 1).await?;
+        cache.set("b", 2).await?;
+        drop(cache);
+
+        // a torn write.
+        let path = data_files(&dir).pop().unwrap();
+        let len = std::fs::metadata(&path)?.len();
+        OpenOptions::new().write(true).open(&path)?.set_len(len - 1)?;
+
+        let cache = BitcaskCacheBuilder::new(&dir).finish().await?;
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+        assert_eq!(cache.get::<u8>("b").await?, None);
+        assert!(std::fs::metadata(&path)?.len() < len - 1);
+
+        // a corrupted record.
+        cache.set("c", 3).await?;
+        drop(cache);
+        let path = data_files(&dir).pop().unwrap();
+        let mut bytes = std::fs::read(&path)?;
+        *bytes.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, bytes)?;
+
+        let cache = BitcaskCacheBuilder::new(&dir).finish().await?;
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+        assert_eq!(cache.get::<u8>("c").await?, None);
+
+        // a corrupted length, which is truncated like a torn record.
+        cache.set("d", 4).await?;
+        drop(cache);
+        let path = data_files(&dir).pop().unwrap();
+        let mut bytes = std::fs::read(&path)?;
+        bytes[16..20].copy_from_slice(&(u32::MAX - 1).to_be_bytes());
+        std::fs::write(&path, bytes)?;
+
+        let cache = BitcaskCacheBuilder::new(&dir).finish().await?;
+        assert_eq!(cache.get::<u8>("d").await?, None);
+        assert_eq!(std::fs::metadata(&path)?.len(), 0);
+        assert_eq!(cache.len().await?, 1);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitcask_cache_compaction() -> anyhow::Result<()> {
+        let dir = temp_dir("bitcask-compaction");
+        let cache = BitcaskCacheBuilder::new(&dir)
+            .max_file_size(256)
+            .finish()
+            .await?;
+
+        for i in 0..100u32 {
+            cache.set(i % 10, i).await?;
+        }
+        cache.delete(9).await?;
+        cache.set_with_ttl("expired", 1, Duration::from_millis(1)).await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let before = data_files(&dir).len();
+        assert!(cache.inner.should_compact(0.5));
+        cache.compact().await?;
+        assert!(data_files(&dir).len() < before);
+        assert!(!cache.inner.should_compact(0.5));
+
+        for i in 0..9u32 {
+            assert_eq!(cache.get::<u32>(i).await?, Some(90 + i));
+        }
+        assert_eq!(cache.get::<u32>(9).await?, None);
+        assert_eq!(cache.len().await?, 9);
+
+        // writes after compaction win over the merged records.
+        cache.set(0, 1000u32).await?;
+        cache.delete(1).await?;
+
+        // reloaded from the hint files.
+        drop(cache);
+        let cache = BitcaskCacheBuilder::new(&dir).finish().await?;
+        assert_eq!(cache.get::<u32>(0).await?, Some(1000));
+        assert_eq!(cache.get::<u32>(1).await?, None);
+        assert_eq!(cache.get::<u32>(2).await?, Some(92));
+        assert_eq!(cache.len().await?, 8);
+
+        // compacted again, after merged files.
+        cache.compact().await?;
+        assert_eq!(cache.get::<u32>(0).await?, Some(1000));
+        assert_eq!(cache.get::<u32>(8).await?, Some(98));
+        assert_eq!(cache.len().await?, 8);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitcask_cache_limits() -> anyhow::Result<()> {
+        let dir = temp_dir("bitcask-limits");
+        let cache = BitcaskCacheBuilder::new(&dir).finish().await?;
+
+        // a huge ttl never expires.
+        cache.set_with_ttl("a", 1, Duration::MAX).await?;
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+        assert_eq!(expires_at(Duration::MAX), 0);
+        assert!(expires_at(Duration::from_secs(60)) > unix_millis(SystemTime::now()));
+
+        // lengths which don't fit the record header.
+        let max = u32::MAX as usize - RECORD_HEADER_LEN;
+        assert!(check_record_len(1, max - 1).is_ok());
+        assert!(check_record_len(1, max).is_err());
+        assert!(check_record_len(0, TOMBSTONE as usize).is_err());
+        assert!(check_record_len(u32::MAX as usize, 0).is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitcask_cache_write_during_compaction() -> anyhow::Result<()> {
+        let dir = temp_dir("bitcask-write-during-compaction");
+        let cache = BitcaskCacheBuilder::new(&dir).finish().await?;
+        for i in 0..4u32 {
+            cache.set(i, i).await?;
+        }
+
+        let compaction = cache.inner.prepare_compaction()?.unwrap();
+        // the state isn't locked during the merge.
+        cache.set(0, 100u32).await?;
+        cache.delete(1).await?;
+        assert_eq!(cache.len().await?, 3);
+
+        let merged = cache.inner.merge(compaction)?;
+        cache.inner.install(merged)?;
+
+        assert_eq!(cache.get::<u32>(0).await?, Some(100));
+        assert_eq!(cache.get::<u32>(1).await?, None);
+        assert_eq!(cache.get::<u32>(2).await?, Some(2));
+        assert_eq!(cache.len().await?, 3);
+
+        // the merged records of `0` and `1` are stale.
+        drop(cache);
+        let cache = BitcaskCacheBuilder::new(&dir).finish().await?;
+        assert_eq!(cache.get::<u32>(0).await?, Some(100));
+        assert_eq!(cache.get::<u32>(1).await?, None);
+        assert_eq!(cache.get::<u32>(3).await?, Some(3));
+        assert!(cache.inner.should_compact(0.1));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitcask_cache_background_compaction() -> anyhow::Result<()> {
+        let dir = temp_dir("bitcask-background");
+        let cache = BitcaskCacheBuilder::new(&dir)
+            .max_file_size(256)
+            .compaction_interval(Duration::from_millis(10))
+            .finish()
+            .await?;
+
+        for i in 0..100u32 {
+            cache.set("a", i).await?;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(!cache.inner.should_compact(0.5));
+        assert_eq!(cache.get::<u32>("a").await?, Some(99));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::{Cache, CacheKey, Cacheable};
+
+const MAGIC: [u8; 4] = [0xca, 0xa7, 0xc4, 0xec];
+
+const CRC32C: u8 = 1;
+const HMAC_SHA256: u8 = 2;
+
+type MismatchHandler = Arc<dyn Fn(&[u8]) + Send + Sync>;
+
+/// Integrity checks used by [`Checked`].
+#[derive(Clone)]
+pub enum Integrity {
+    /// A CRC32C checksum of the value. It detects corruption, but not tampering.
+    Crc32c,
+    /// An HMAC-SHA256 signature of the key and the value, using the given secret.
+    /// It detects corruption and tampering, including values moved from another key.
+    HmacSha256(Arc<[u8]>),
+}
+
+impl Debug for Integrity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Integrity::Crc32c => write!(f, "Crc32c"),
+            Integrity::HmacSha256(_) => write!(f, "HmacSha256"),
+        }
+    }
+}
+
+/// A cache wrapper that verifies the integrity of values.
+///
+/// [`Checked::set`] writes an envelope with a checksum or a signature (see [`Integrity`]),
+/// which is verified on every [`Checked::get`]. If the verification fails,
+/// the value is treated as a miss, and:
+///
+/// * the mismatch counter is increased. See [`Checked::mismatches`].
+/// * the handler set by [`Checked::on_mismatch`] is called with the key.
+/// * the value is deleted if [`Checked::delete_on_mismatch`] is set.
+///
+/// Values written without the wrapper are treated as mismatches too.
+/// Feature `integrity` must be enabled.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache = Checked::new(RedisCache::new(client, "aaa").await?, Integrity::Crc32c)
+///     .delete_on_mismatch(true)
+///     .on_mismatch(|key| eprintln!("corrupted value: {:?}", key));
+///
+/// cache.set("a", 1).await?;
+/// assert_eq!(cache.get::<u8>("a").await?, Some(1));
+/// ```
+#[derive(Clone)]
+pub struct Checked<C> {
+    inner: C,
+    integrity: Integrity,
+    delete_on_mismatch: bool,
+    on_mismatch: Option<MismatchHandler>,
+    mismatches: Arc<AtomicU64>,
+}
+
+impl<C: Debug> Debug for Checked<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Checked")
+            .field("inner", &self.inner)
+            .field("integrity", &self.integrity)
+            .field("delete_on_mismatch", &self.delete_on_mismatch)
+            .field("mismatches", &self.mismatches)
+            .finish()
+    }
+}
+
+impl<C: Cache> Checked<C> {
+    pub fn new(inner: C, integrity: Integrity) -> Self {
+        Self {
+            inner,
+            integrity,
+            delete_on_mismatch: false,
+            on_mismatch: None,
+            mismatches: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Delete values which fail the verification. It's `false` by default.
+    pub fn delete_on_mismatch(mut self, delete: bool) -> Self {
+        self.delete_on_mismatch = delete;
+        self
+    }
+
+    /// Set a handler called with the key of every value which fails the verification.
+    pub fn on_mismatch<F: Fn(&[u8]) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.on_mismatch = Some(Arc::new(f));
+        self
+    }
+
+    /// The number of values which failed the verification.
+    /// It's shared by clones of this cache.
+    pub fn mismatches(&self) -> u64 {
+        self.mismatches.load(Ordering::Relaxed)
+    }
+
+    /// Get the wrapped cache.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Unwrap and return the wrapped cache.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn seal(&self, key: &[u8], payload: &[u8]) -> Vec<u8> {
+        let (algorithm, checksum) = match &self.integrity {
+            Integrity::Crc32c => (CRC32C, crc32c::crc32c(payload).to_be_bytes().to_vec()),
+            Integrity::HmacSha256(secret) => (HMAC_SHA256, hmac_sha256(secret, key, payload)),
+        };
+
+        let mut ret = Vec::with_capacity(MAGIC.len() + 1 + checksum.len() + payload.len());
+        ret.extend_from_slice(&MAGIC);
+        ret.push(algorithm);
+        ret.extend_from_slice(&checksum);
+        ret.extend_from_slice(payload);
+        ret
+    }
+
+    /// Returns the payload if the verification passes.
+    fn open<'a>(&self, key: &[u8], bytes: &'a [u8]) -> Option<&'a [u8]> {
+        let (&algorithm, rest) = bytes.strip_prefix(&MAGIC)?.split_first()?;
+
+        match (&self.integrity, algorithm) {
+            (Integrity::Crc32c, CRC32C) => {
+                let (checksum, payload) = split_at_checked(rest, 4)?;
+                let expected = crc32c::crc32c(payload).to_be_bytes();
+                (checksum == expected).then_some(payload)
+            },
+            (Integrity::HmacSha256(secret), HMAC_SHA256) => {
+                let (signature, payload) = split_at_checked(rest, 32)?;
+                let mut mac = hmac(secret, key);
+                mac.update(payload);
+                mac.verify_slice(signature).ok().map(|_| payload)
+            },
+            _ => None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<C: Cache + Send + Sync> Cache for Checked<C> {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let key = key.to_key();
+        let Some(bytes) = self.inner.get::<Vec<u8>>(&key).await? else {
+            return Ok(None);
+        };
+
+        match self.open(&key, &bytes) {
+            Some(payload) => Ok(Some(T::from_bytes(payload)?)),
+            None => {
+                self.mismatches.fetch_add(1, Ordering::Relaxed);
+                if let Some(on_mismatch) = &self.on_mismatch {
+                    on_mismatch(&key);
+                }
+                if self.delete_on_mismatch {
+                    self.inner.delete(&key).await?;
+                }
+
+                Ok(None)
+            },
+        }
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        let key = key.to_key();
+        let bytes = self.seal(&key, &value.to_bytes());
+        self.inner.set(key, bytes).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        self.inner.delete(key).await
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        self.inner.len().await
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        let key = key.to_key();
+        let bytes = self.seal(&key, &value.to_bytes());
+        self.inner.set_with_ttl(key, bytes, ttl).await
+    }
+}
+
+fn split_at_checked(bytes: &[u8], mid: usize) -> Option<(&[u8], &[u8])> {
+    (bytes.len() >= mid).then(|| bytes.split_at(mid))
+}
+
+/// The key is length-prefixed, so that the boundary between the key and the value is fixed.
+fn hmac(secret: &[u8], key: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret)
+        .expect("hmac accepts keys of any length");
+    mac.update(&(key.len() as u64).to_be_bytes());
+    mac.update(key);
+    mac
+}
+
+fn hmac_sha256(secret: &[u8], key: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut mac = hmac(secret, key);
+    mac.update(payload);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use crate::MemoryCache;
+    use super::*;
+
+    fn integrities() -> Vec<Integrity> {
+        vec![
+            Integrity::Crc32c,
+            Integrity::HmacSha256(Arc::from(&b"secret"[..])),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_checked() -> anyhow::Result<()> {
+        for integrity in integrities() {
+            let cache = Checked::new(MemoryCache::default(), integrity);
+
+            cache.set("a", 1u64).await?;
+            cache.set("b", String::from("bbb")).await?;
+            cache.set("c", ()).await?;
+
+            assert_eq!(cache.get::<u64>("a").await?, Some(1));
+            assert_eq!(cache.get::<String>("b").await?, Some(String::from("bbb")));
+            assert_eq!(cache.get::<()>("c").await?, Some(()));
+            assert_eq!(cache.get::<()>("none").await?, None);
+            assert_eq!(cache.mismatches(), 0);
+
+            cache.delete("a").await?;
+            assert_eq!(cache.len().await?, 2);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checked_mismatch() -> anyhow::Result<()> {
+        for integrity in integrities() {
+            let inner = MemoryCache::default();
+            let keys = Arc::new(Mutex::new(Vec::new()));
+            let handler_keys = keys.clone();
+            let cache = Checked::new(inner.clone(), integrity)
+                .on_mismatch(move |key| handler_keys.lock().unwrap().push(key.to_vec()));
+
+            cache.set("a", 1u64).await?;
+            let mut bytes = inner.get::<Vec<u8>>("a").await?.unwrap();
+            *bytes.last_mut().unwrap() ^= 1;
+            inner.set("a", bytes).await?;
+            inner.set("legacy", 1u64).await?;
+            inner.set("short", vec![0xca, 0xa7, 0xc4, 0xec, 2]).await?;
+
+            assert_eq!(cache.get::<u64>("a").await?, None);
+            assert_eq!(cache.get::<u64>("legacy").await?, None);
+            assert_eq!(cache.get::<Vec<u8>>("short").await?, None);
+            assert_eq!(cache.mismatches(), 3);
+            assert_eq!(*keys.lock().unwrap(), vec![b"a".to_vec(), b"legacy".to_vec(), b"short".to_vec()]);
+
+            // the entry is kept by default.
+            assert_eq!(inner.len().await?, 3);
+            let cache = cache.delete_on_mismatch(true);
+            assert_eq!(cache.get::<u64>("a").await?, None);
+            assert_eq!(inner.get::<Vec<u8>>("a").await?, None);
+            assert_eq!(cache.clone().mismatches(), 4);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checked_hmac() -> anyhow::Result<()> {
+        let inner = MemoryCache::default();
+        let cache = Checked::new(inner.clone(), Integrity::HmacSha256(Arc::from(&b"secret"[..])));
+
+        // moved from another key.
+        cache.set("a", 1u64).await?;
+        inner.set("b", inner.get::<Vec<u8>>("a").await?.unwrap()).await?;
+        assert_eq!(cache.get::<u64>("b").await?, None);
+
+        // signed with another secret.
+        let other = Checked::new(inner.clone(), Integrity::HmacSha256(Arc::from(&b"other"[..])));
+        assert_eq!(other.get::<u64>("a").await?, None);
+
+        // checksummed instead of signed.
+        let crc = Checked::new(inner.clone(), Integrity::Crc32c);
+        crc.set("c", 1u64).await?;
+        assert_eq!(cache.get::<u64>("c").await?, None);
+        assert_eq!(cache.mismatches(), 2);
+
+        Ok(())
+    }
+}
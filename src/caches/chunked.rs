@@ -0,0 +1,558 @@
+use std::future::Future;
+use std::io::{Cursor, Read};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use tokio::io::{AsyncRead, ReadBuf};
+use crate::{Cache, CacheKey, Cacheable, ValueReader};
+
+const MAGIC: [u8; 4] = [0xca, 0xa7, 0xc8, 0x4b];
+
+const INLINE: u8 = 0;
+const MANIFEST: u8 = 1;
+
+/// A cache wrapper that splits large values into chunks.
+///
+/// Values larger than the chunk size (16 KiB by default) are split into numbered
+/// sub-entries, and a manifest is stored under the original key.
+/// [`Chunked::get`] reassembles the value, and [`Chunked::delete`] removes the chunks too.
+/// When a chunked value is overwritten, its old chunks are removed after the new
+/// manifest is written, so readers never see a half-written value.
+///
+/// Concurrent writes of the same key are not atomic. A writer whose manifest has
+/// been replaced by another writer's removes its own chunks, but two writers that
+/// replace the same manifest at the same moment may still leave the losing
+/// writer's chunks behind. They are counted by [`Chunked::len`] until they expire
+/// (if written with a ttl) or the wrapped cache evicts them.
+///
+/// It lifts the size limits of backends, for example the `text` column of
+/// [`crate::MySqlCache`] (a 16 KiB value is 32 KiB in hex), or keeps huge fields
+/// out of [`crate::RedisCache`].
+///
+/// Chunks are stored under `{key}#chunk:{generation}:{index}`.
+/// They are counted by [`Chunked::len`], which returns the length of the wrapped cache.
+/// If a chunk is missing (for example, evicted), the value is treated as a miss.
+///
+/// [`Cache::get_stream`] and [`Cache::set_stream`] are native: only one chunk is kept
+/// in memory at a time. A chunk missing in the middle of a stream is reported as
+/// an [`std::io::ErrorKind::UnexpectedEof`] error by the reader.
+/// Streamed values are chunked exactly like [`Chunked::set`] would, and like it,
+/// they never expire, as [`Cache::set_stream`] takes no ttl.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache = Chunked::new(MySqlCacheBuilder::new(pool).finish())
+///     .chunk_size(8 * 1024);
+///
+/// cache.set("report", report).await?;
+/// let report: Vec<u8> = cache.get("report").await?.unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Chunked<C> {
+    inner: C,
+    chunk_size: usize,
+}
+
+/// The header stored under the original key of a chunked value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Manifest {
+    generation: u64,
+    chunks: u32,
+    len: u64,
+}
+
+impl<C: Cache + Send + Sync + 'static> Chunked<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            chunk_size: 16 * 1024,
+        }
+    }
+
+    /// Set the chunk size in bytes. Values not larger than it are stored in one entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must be positive");
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Get the wrapped cache.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Unwrap and return the wrapped cache.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    async fn manifest(&self, key: &[u8]) -> anyhow::Result<Option<Manifest>> {
+        let bytes: Option<Vec<u8>> = self.inner.get(key).await?;
+        Ok(bytes.and_then(|bytes| Manifest::decode(&bytes)))
+    }
+
+    async fn put(&self, key: Vec<u8>, bytes: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        match ttl {
+            None => self.inner.set(key, bytes).await,
+            Some(ttl) => self.inner.set_with_ttl(key, bytes, ttl).await,
+        }
+    }
+
+    async fn set_bytes(&self, key: &[u8], bytes: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        if !bytes.starts_with(&MAGIC) {
+            return self.put(key.to_vec(), bytes, ttl).await;
+        }
+
+        // tag it, so that it's not mistaken for a manifest.
+        let mut tagged = Vec::with_capacity(MAGIC.len() + 1 + bytes.len());
+        tagged.extend_from_slice(&MAGIC);
+        tagged.push(INLINE);
+        tagged.extend_from_slice(&bytes);
+        self.put(key.to_vec(), tagged, ttl).await
+    }
+
+    /// Chunks expire together with the manifest.
+    async fn store(&self, key: Vec<u8>, bytes: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        if bytes.len() <= self.chunk_size {
+            let old = self.manifest(&key).await?;
+            self.set_bytes(&key, bytes, ttl).await?;
+            self.replaced(&key, old.as_ref(), None).await?;
+        } else {
+            let manifest = Manifest {
+                generation: generation(),
+                chunks: bytes.chunks(self.chunk_size).len() as u32,
+                len: bytes.len() as u64,
+            };
+
+            for (index, chunk) in bytes.chunks(self.chunk_size).enumerate() {
+                self.put(chunk_key(&key, manifest.generation, index as u32), chunk.to_vec(), ttl).await?;
+            }
+
+            let old = self.manifest(&key).await?;
+            self.put(key.clone(), manifest.encode(), ttl).await?;
+            self.replaced(&key, old.as_ref(), Some(&manifest)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Clean up after the manifest `old` was replaced by `new` (`None` for an unchunked value).
+    ///
+    /// The old chunks are removed. If another writer has replaced `new` in the meantime,
+    /// its chunks are removed as well, since nobody else knows about them.
+    async fn replaced(&self, key: &[u8], old: Option<&Manifest>, new: Option<&Manifest>) -> anyhow::Result<()> {
+        if let Some(old) = old {
+            self.delete_chunks(key, old).await?;
+        }
+
+        if let Some(new) = new {
+            if self.manifest(key).await?.as_ref() != Some(new) {
+                self.delete_chunks(key, new).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_chunks(&self, key: &[u8], manifest: &Manifest) -> anyhow::Result<()> {
+        for index in 0..manifest.chunks {
+            self.inner.delete(chunk_key(key, manifest.generation, index)).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<C: Cache + Send + Sync + 'static> Cache for Chunked<C> {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let key = key.to_key();
+        let Some(bytes) = self.inner.get::<Vec<u8>>(&key).await? else {
+            return Ok(None);
+        };
+
+        let manifest = match bytes.strip_prefix(&MAGIC).and_then(|rest| rest.split_first()) {
+            Some((&INLINE, payload)) => return Ok(Some(T::from_bytes(payload)?)),
+            Some((&MANIFEST, _)) => Manifest::decode(&bytes)
+                .ok_or_else(|| anyhow::anyhow!("invalid chunk manifest"))?,
+            // not chunked, or written without the wrapper.
+            _ => return Ok(Some(T::from_bytes(&bytes)?)),
+        };
+
+        // the length comes from the backing store, so the value grows as chunks arrive.
+        let mut value = Vec::new();
+        for index in 0..manifest.chunks {
+            let chunk: Option<Vec<u8>> = self.inner.get(chunk_key(&key, manifest.generation, index)).await?;
+            let Some(chunk) = chunk else {
+                return Ok(None);
+            };
+            value.extend_from_slice(&chunk);
+            if value.len() as u64 > manifest.len {
+                return Ok(None);
+            }
+        }
+
+        if value.len() as u64 != manifest.len {
+            return Ok(None);
+        }
+
+        Ok(Some(T::from_bytes(&value)?))
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        self.store(key.to_key(), value.to_bytes(), None).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        let key = key.to_key();
+        let manifest = self.manifest(&key).await?;
+
+        self.inner.delete(&key).await?;
+        if let Some(manifest) = manifest {
+            self.delete_chunks(&key, &manifest).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        self.inner.len().await
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        self.store(key.to_key(), value.to_bytes(), Some(ttl)).await
+    }
+
+    async fn get_stream(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<ValueReader>> {
+        let key = key.to_key();
+        let Some(bytes) = self.inner.get::<Vec<u8>>(&key).await? else {
+            return Ok(None);
+        };
+
+        let manifest = match bytes.strip_prefix(&MAGIC).and_then(|rest| rest.split_first()) {
+            Some((&INLINE, payload)) => return Ok(Some(Box::new(Cursor::new(payload.to_vec())))),
+            Some((&MANIFEST, _)) => Manifest::decode(&bytes)
+                .ok_or_else(|| anyhow::anyhow!("invalid chunk manifest"))?,
+            _ => return Ok(Some(Box::new(Cursor::new(bytes)))),
+        };
+
+        Ok(Some(Box::new(ChunkReader {
+            cache: self.inner.clone(),
+            key,
+            manifest,
+            index: 0,
+            read: 0,
+            chunk: Cursor::new(Vec::new()),
+            pending: None,
+        })))
+    }
+
+    async fn set_stream(&self, key: impl CacheKey + Send + Sync, mut reader: impl AsyncRead + Send + Unpin) -> anyhow::Result<()> {
+        // imported here, as it conflicts with `ReadBytesExt`.
+        use tokio::io::AsyncReadExt;
+
+        let key = key.to_key();
+
+        // one more byte than a chunk tells whether the value fits in one entry, as in `store`.
+        let mut pending = Vec::with_capacity(self.chunk_size + 1);
+        (&mut reader).take(self.chunk_size as u64 + 1).read_to_end(&mut pending).await?;
+        if pending.len() <= self.chunk_size {
+            let old = self.manifest(&key).await?;
+            self.set_bytes(&key, pending, None).await?;
+            self.replaced(&key, old.as_ref(), None).await?;
+            return Ok(());
+        }
+
+        let generation = generation();
+        let mut chunks = 0u32;
+        let mut len = 0u64;
+        loop {
+            if pending.len() < self.chunk_size {
+                let missing = self.chunk_size - pending.len();
+                (&mut reader).take(missing as u64).read_to_end(&mut pending).await?;
+            }
+            if pending.is_empty() {
+                break;
+            }
+
+            let rest = pending.split_off(pending.len().min(self.chunk_size));
+            len += pending.len() as u64;
+            self.inner.set(chunk_key(&key, generation, chunks), pending).await?;
+            chunks += 1;
+            pending = rest;
+        }
+
+        let manifest = Manifest { generation, chunks, len };
+        let old = self.manifest(&key).await?;
+        self.inner.set(&key, manifest.encode()).await?;
+        self.replaced(&key, old.as_ref(), Some(&manifest)).await?;
+
+        Ok(())
+    }
+}
+
+type ChunkFuture = Pin<Box<dyn Future<Output = anyhow::Result<Option<Vec<u8>>>> + Send>>;
+
+/// Reads a chunked value, fetching one chunk at a time.
+struct ChunkReader<C> {
+    cache: C,
+    key: Vec<u8>,
+    manifest: Manifest,
+    index: u32,
+    read: u64,
+    chunk: Cursor<Vec<u8>>,
+    pending: Option<ChunkFuture>,
+}
+
+// `cache` is never pinned.
+impl<C> Unpin for ChunkReader<C> {}
+
+impl<C: Cache + Send + Sync + 'static> AsyncRead for ChunkReader<C> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        loop {
+            let position = self.chunk.position() as usize;
+            let remaining = &self.chunk.get_ref()[position..];
+            if !remaining.is_empty() {
+                let n = remaining.len().min(buf.remaining());
+                buf.put_slice(&remaining[..n]);
+                self.chunk.set_position((position + n) as u64);
+                return Poll::Ready(Ok(()));
+            }
+
+            if let Some(pending) = self.pending.as_mut() {
+                let chunk = match pending.as_mut().poll(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(chunk) => chunk,
+                };
+                self.pending = None;
+
+                let chunk = chunk.map_err(std::io::Error::other)?
+                    .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "chunk is missing"))?;
+                self.index += 1;
+                self.read += chunk.len() as u64;
+                self.chunk = Cursor::new(chunk);
+                continue;
+            }
+
+            if self.index == self.manifest.chunks {
+                if self.read != self.manifest.len {
+                    return Poll::Ready(Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "chunked value has a wrong length")));
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            let cache = self.cache.clone();
+            let key = chunk_key(&self.key, self.manifest.generation, self.index);
+            self.pending = Some(Box::pin(async move { cache.get::<Vec<u8>>(key).await }));
+        }
+    }
+}
+
+impl Manifest {
+    fn encode(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(MAGIC.len() + 1 + 8 + 4 + 8);
+        ret.extend_from_slice(&MAGIC);
+        ret.push(MANIFEST);
+        ret.write_u64::<BigEndian>(self.generation).unwrap();
+        ret.write_u32::<BigEndian>(self.chunks).unwrap();
+        ret.write_u64::<BigEndian>(self.len).unwrap();
+        ret
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut cursor = Cursor::new(bytes.strip_prefix(&MAGIC)?);
+        if cursor.read_u8().ok()? != MANIFEST {
+            return None;
+        }
+
+        let manifest = Self {
+            generation: cursor.read_u64::<BigEndian>().ok()?,
+            chunks: cursor.read_u32::<BigEndian>().ok()?,
+            len: cursor.read_u64::<BigEndian>().ok()?,
+        };
+
+        // trailing bytes are not expected.
+        cursor.read(&mut [0]).ok().filter(|&n| n == 0)?;
+        Some(manifest)
+    }
+}
+
+/// Chunk keys are text, so that they are accepted by text-keyed backends such as mysql.
+fn chunk_key(key: &[u8], generation: u64, index: u32) -> Vec<u8> {
+    let mut ret = key.to_vec();
+    ret.extend_from_slice(format!("#chunk:{:016x}:{}", generation, index).as_bytes());
+    ret
+}
+
+/// A generation identifies one write of a chunked value,
+/// so that chunks of an old value are never mixed with a new one.
+fn generation() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default();
+    nanos ^ COUNTER.fetch_add(1, Ordering::Relaxed).rotate_right(16)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MemoryCache;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_chunked() -> anyhow::Result<()> {
+        let inner = MemoryCache::default();
+        let cache = Chunked::new(inner.clone()).chunk_size(16);
+
+        let large: Vec<u8> = (0..100).collect();
+        cache.set("large", large.clone()).await?;
+        cache.set("small", String::from("small")).await?;
+
+        assert_eq!(cache.get::<Vec<u8>>("large").await?, Some(large.clone()));
+        assert_eq!(cache.get::<String>("small").await?, Some(String::from("small")));
+        assert_eq!(cache.get::<String>("none").await?, None);
+
+        // 7 chunks, 1 manifest and 1 small value.
+        assert_eq!(inner.len().await?, 9);
+        assert_eq!(inner.get::<String>("small").await?, Some(String::from("small")));
+
+        cache.delete("large").await?;
+        assert_eq!(cache.get::<Vec<u8>>("large").await?, None);
+        assert_eq!(inner.len().await?, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunked_overwrite() -> anyhow::Result<()> {
+        let inner = MemoryCache::default();
+        let cache = Chunked::new(inner.clone()).chunk_size(16);
+
+        cache.set("a", vec![1u8; 100]).await?;
+        cache.set("a", vec![2u8; 40]).await?;
+        assert_eq!(cache.get::<Vec<u8>>("a").await?, Some(vec![2u8; 40]));
+        assert_eq!(inner.len().await?, 4);
+
+        cache.set("a", 1u64).await?;
+        assert_eq!(cache.get::<u64>("a").await?, Some(1));
+        assert_eq!(inner.len().await?, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunked_missing_chunk() -> anyhow::Result<()> {
+        let inner = MemoryCache::default();
+        let cache = Chunked::new(inner.clone()).chunk_size(16);
+
+        cache.set("a", vec![1u8; 100]).await?;
+        let manifest = Manifest::decode(&inner.get::<Vec<u8>>("a").await?.unwrap()).unwrap();
+        assert_eq!(manifest.chunks, 7);
+        assert_eq!(manifest.len, 100);
+
+        inner.delete(chunk_key(b"a", manifest.generation, 3)).await?;
+        assert_eq!(cache.get::<Vec<u8>>("a").await?, None);
+
+        // a small value that looks like a manifest.
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(MANIFEST);
+        cache.set("magic", bytes.clone()).await?;
+        assert_eq!(cache.get::<Vec<u8>>("magic").await?, Some(bytes));
+
+        // a manifest claiming a shorter (or huge) length than its chunks.
+        for len in [10, u64::MAX] {
+            let forged = Manifest { len, ..manifest.clone() };
+            cache.set("b", vec![1u8; 100]).await?;
+            let manifest = Manifest::decode(&inner.get::<Vec<u8>>("b").await?.unwrap()).unwrap();
+            inner.set("b", Manifest { generation: manifest.generation, ..forged }.encode()).await?;
+            assert_eq!(cache.get::<Vec<u8>>("b").await?, None);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunked_lost_write() -> anyhow::Result<()> {
+        let inner = MemoryCache::default();
+        let cache = Chunked::new(inner.clone()).chunk_size(16);
+
+        cache.set("a", vec![1u8; 100]).await?;
+        let lost = Manifest::decode(&inner.get::<Vec<u8>>("a").await?.unwrap()).unwrap();
+
+        // another writer replaced the manifest before the chunks were cleaned up.
+        inner.set("a", 1u64).await?;
+        cache.replaced(b"a", None, Some(&lost)).await?;
+        assert_eq!(inner.len().await?, 1);
+        assert_eq!(cache.get::<u64>("a").await?, Some(1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunked_stream() -> anyhow::Result<()> {
+        use tokio::io::AsyncReadExt;
+
+        let inner = MemoryCache::default();
+        let cache = Chunked::new(inner.clone()).chunk_size(16);
+
+        let large: Vec<u8> = (0..100).collect();
+        cache.set_stream("a", &large[..]).await?;
+        cache.set_stream("b", &large[..10]).await?;
+        cache.set_stream("c", &large[..32]).await?;
+        assert_eq!(inner.len().await?, 7 + 1 + 1 + 2 + 1);
+
+        for (key, expected) in [("a", &large[..]), ("b", &large[..10]), ("c", &large[..32])] {
+            assert_eq!(cache.get::<Vec<u8>>(key).await?.as_deref(), Some(expected));
+
+            let mut bytes = Vec::new();
+            cache.get_stream(key).await?.unwrap().read_to_end(&mut bytes).await?;
+            assert_eq!(bytes, expected);
+        }
+        assert!(cache.get_stream("none").await?.is_none());
+
+        // overwritten by a stream.
+        cache.set_stream("a", &large[..20]).await?;
+        assert_eq!(cache.get::<Vec<u8>>("a").await?, Some(large[..20].to_vec()));
+        assert_eq!(inner.len().await?, 2 + 1 + 1 + 2 + 1);
+
+        let manifest = Manifest::decode(&inner.get::<Vec<u8>>("a").await?.unwrap()).unwrap();
+        inner.delete(chunk_key(b"a", manifest.generation, 1)).await?;
+        let mut bytes = Vec::new();
+        let err = cache.get_stream("a").await?.unwrap().read_to_end(&mut bytes).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunked_stream_boundary() -> anyhow::Result<()> {
+        let large: Vec<u8> = (0..100).collect();
+
+        // values of exactly one chunk are inlined by both `set` and `set_stream`.
+        for len in [15, 16, 17, 32, 33] {
+            let inner = MemoryCache::default();
+            let cache = Chunked::new(inner.clone()).chunk_size(16);
+            cache.set("a", large[..len].to_vec()).await?;
+            cache.set_stream("b", &large[..len]).await?;
+
+            let entries = if len <= 16 { 1 } else { len.div_ceil(16) + 1 };
+            assert_eq!(inner.len().await?, entries * 2);
+            assert_eq!(cache.get::<Vec<u8>>("b").await?, Some(large[..len].to_vec()));
+            assert_eq!(
+                Manifest::decode(&inner.get::<Vec<u8>>("a").await?.unwrap()).map(|manifest| (manifest.chunks, manifest.len)),
+                Manifest::decode(&inner.get::<Vec<u8>>("b").await?.unwrap()).map(|manifest| (manifest.chunks, manifest.len)),
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+use std::time::Duration;
+use crate::{Cache, CacheKey, Cacheable};
+
+const MAGIC: [u8; 4] = [0xca, 0xa7, 0xc0, 0x3d];
+
+const STORED: u8 = 0;
+const ZSTD: u8 = 1;
+const LZ4: u8 = 2;
+
+/// Compression algorithms used by [`Compressed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Use zstd with the given level. Feature `zstd` must be enabled.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+    /// Use lz4. Feature `lz4` must be enabled.
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+/// A cache wrapper that compresses values.
+///
+/// Values larger than the threshold (1 KiB by default) are compressed,
+/// and are tagged with a header naming the algorithm.
+/// Smaller values, and values written without the wrapper, are stored as is
+/// and remain readable.
+///
+/// Values which decompress to more than the maximum size (64 MiB by default) are rejected,
+/// so that a small corrupted or malicious value can't exhaust memory.
+///
+/// It works over any [`Cache`], for example [`crate::MemoryCache`],
+/// [`crate::RedisCache`] and [`crate::MySqlCache`].
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache = Compressed::new(MemoryCache::default(), Compression::Zstd(3))
+///     .threshold(4096);
+///
+/// cache.set("doc", json).await?;
+/// let doc: String = cache.get("doc").await?.unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Compressed<C> {
+    inner: C,
+    compression: Compression,
+    threshold: usize,
+    max_size: usize,
+}
+
+impl<C: Cache> Compressed<C> {
+    pub fn new(inner: C, compression: Compression) -> Self {
+        Self {
+            inner,
+            compression,
+            threshold: 1024,
+            max_size: 64 << 20,
+        }
+    }
+
+    /// Set the threshold in bytes. Values not larger than it are not compressed.
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set the maximum size in bytes of decompressed values. Larger values are rejected.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Get the wrapped cache.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Unwrap and return the wrapped cache.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn compress(&self, bytes: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        if bytes.len() <= self.threshold {
+            if !bytes.starts_with(&MAGIC) {
+                return Ok(bytes);
+            }
+
+            // tag it, so that it's not mistaken for a compressed value.
+            return Ok(with_header(STORED, &bytes));
+        }
+
+        let ret = match self.compression {
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => with_header(ZSTD, &zstd::bulk::compress(&bytes, level)?),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => with_header(LZ4, &lz4_flex::compress_prepend_size(&bytes)),
+        };
+
+        Ok(ret)
+    }
+
+    fn decompress(&self, bytes: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        let Some((&algorithm, payload)) = bytes.strip_prefix(&MAGIC).and_then(|rest| rest.split_first()) else {
+            // not compressed, or written without the wrapper.
+            return Ok(bytes);
+        };
+
+        match algorithm {
+            STORED => Ok(payload.to_vec()),
+            #[cfg(feature = "zstd")]
+            ZSTD => {
+                use std::io::Read;
+
+                let mut ret = Vec::new();
+                let limit = u64::try_from(self.max_size).unwrap_or(u64::MAX).saturating_add(1);
+                zstd::stream::read::Decoder::new(payload)?.take(limit).read_to_end(&mut ret)?;
+                self.check_size(ret.len())?;
+                Ok(ret)
+            }
+            #[cfg(feature = "lz4")]
+            LZ4 => {
+                let (size, _) = lz4_flex::block::uncompressed_size(payload)?;
+                self.check_size(size)?;
+                Ok(lz4_flex::decompress_size_prepended(payload)?)
+            }
+            #[cfg(not(feature = "zstd"))]
+            ZSTD => anyhow::bail!("value is compressed by zstd, but feature `zstd` is not enabled"),
+            #[cfg(not(feature = "lz4"))]
+            LZ4 => anyhow::bail!("value is compressed by lz4, but feature `lz4` is not enabled"),
+            _ => anyhow::bail!("unknown compression algorithm: {}", algorithm),
+        }
+    }
+
+    fn check_size(&self, size: usize) -> anyhow::Result<()> {
+        if size > self.max_size {
+            anyhow::bail!("decompressed value is larger than the maximum size {}", self.max_size);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<C: Cache + Send + Sync> Cache for Compressed<C> {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let bytes: Option<Vec<u8>> = self.inner.get(key).await?;
+
+        bytes.map(|bytes| T::from_bytes(&self.decompress(bytes)?))
+            .transpose()
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        let bytes = self.compress(value.to_bytes())?;
+        self.inner.set(key, bytes).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        self.inner.delete(key).await
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        self.inner.len().await
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        let bytes = self.compress(value.to_bytes())?;
+        self.inner.set_with_ttl(key, bytes, ttl).await
+    }
+}
+
+fn with_header(algorithm: u8, payload: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(MAGIC.len() + 1 + payload.len());
+    ret.extend_from_slice(&MAGIC);
+    ret.push(algorithm);
+    ret.extend_from_slice(payload);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MemoryCache;
+    use super::*;
+
+    fn compressions() -> Vec<Compression> {
+        vec![
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(3),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4,
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_compressed() -> anyhow::Result<()> {
+        for compression in compressions() {
+            let inner = MemoryCache::default();
+            let cache = Compressed::new(inner.clone(), compression);
+
+            let doc = "{\"name\":\"jack\"}".repeat(1024);
+            cache.set("doc", doc.clone()).await?;
+            cache.set("small", String::from("small")).await?;
+
+            assert_eq!(cache.get::<String>("doc").await?, Some(doc.clone()));
+            assert_eq!(cache.get::<String>("small").await?, Some(String::from("small")));
+            assert_eq!(cache.get::<String>("none").await?, None);
+
+            let stored = inner.get::<Vec<u8>>("doc").await?.unwrap();
+            assert!(stored.starts_with(&MAGIC));
+            assert!(stored.len() < doc.len() / 10);
+            assert_eq!(inner.get::<String>("small").await?, Some(String::from("small")));
+
+            cache.delete("doc").await?;
+            assert_eq!(cache.len().await?, 1);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compressed_legacy() -> anyhow::Result<()> {
+        for compression in compressions() {
+            let inner = MemoryCache::default();
+            inner.set("legacy", "a".repeat(4096)).await?;
+
+            let cache = Compressed::new(inner.clone(), compression).threshold(16);
+            assert_eq!(cache.get::<String>("legacy").await?, Some("a".repeat(4096)));
+
+            // a small value that looks like a header.
+            let mut bytes = MAGIC.to_vec();
+            bytes.push(ZSTD);
+            cache.set("magic", bytes.clone()).await?;
+            assert_eq!(cache.get::<Vec<u8>>("magic").await?, Some(bytes));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compressed_max_size() -> anyhow::Result<()> {
+        for compression in compressions() {
+            let inner = MemoryCache::default();
+            let cache = Compressed::new(inner.clone(), compression).max_size(4096);
+
+            cache.set("fit", "a".repeat(4096)).await?;
+            assert_eq!(cache.get::<String>("fit").await?, Some("a".repeat(4096)));
+
+            cache.set("large", "a".repeat(4097)).await?;
+            assert!(cache.get::<String>("large").await.is_err());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use crate::{Cache, CacheKey, Cacheable};
+
+const MAGIC: [u8; 4] = [0xca, 0xa7, 0xe0, 0xc1];
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 4 + NONCE_LEN;
+
+/// A cache wrapper that encrypts values.
+///
+/// Values are encrypted by XChaCha20-Poly1305 with a random nonce.
+/// The key of the cache is used as associated data, so an encrypted value
+/// can't be moved to another key.
+///
+/// Keys are identified by ids, which are stored with the values.
+/// To rotate keys, encrypt with a new key and keep the old ones for decryption:
+///
+/// ```rust,ignore
+/// let cache = Encrypted::new(RedisCache::new(client, "pii").await?, 2, new_key)
+///     .decrypt_key(1, old_key);
+///
+/// cache.set("user:1", String::from("jack")).await?;
+/// let name: Option<String> = cache.get("user:1").await?;
+/// ```
+///
+/// If a value can't be decrypted, `get` returns a [`DecryptionError`].
+/// Feature `encryption` must be enabled.
+#[derive(Clone)]
+pub struct Encrypted<C> {
+    inner: C,
+    key_id: u32,
+    keys: Arc<HashMap<u32, XChaCha20Poly1305>>,
+}
+
+impl<C: Debug> Debug for Encrypted<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Encrypted")
+            .field("inner", &self.inner)
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Cache> Encrypted<C> {
+    /// Create an [`Encrypted`] which encrypts values with `key`, identified by `key_id`.
+    pub fn new(inner: C, key_id: u32, key: [u8; 32]) -> Self {
+        let mut keys = HashMap::new();
+        keys.insert(key_id, XChaCha20Poly1305::new(&key.into()));
+
+        Self {
+            inner,
+            key_id,
+            keys: Arc::new(keys),
+        }
+    }
+
+    /// Add a key which is only used for decryption, for example a rotated key.
+    pub fn decrypt_key(mut self, key_id: u32, key: [u8; 32]) -> Self {
+        Arc::make_mut(&mut self.keys)
+            .entry(key_id)
+            .or_insert_with(|| XChaCha20Poly1305::new(&key.into()));
+        self
+    }
+
+    /// Get the wrapped cache.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Unwrap and return the wrapped cache.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn encrypt(&self, key: &[u8], bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let mut ret = Vec::with_capacity(HEADER_LEN + bytes.len() + 16);
+        ret.extend_from_slice(&MAGIC);
+        ret.extend_from_slice(&self.key_id.to_be_bytes());
+        ret.extend_from_slice(&nonce);
+
+        let aad = associated_data(&ret[..MAGIC.len() + 4], key);
+        let cipher = &self.keys[&self.key_id];
+        let ciphertext = cipher.encrypt(&nonce, Payload { msg: bytes, aad: &aad })
+            .map_err(|_| anyhow::anyhow!("failed to encrypt value"))?;
+        ret.extend_from_slice(&ciphertext);
+
+        Ok(ret)
+    }
+
+    fn decrypt(&self, key: &[u8], bytes: &[u8]) -> Result<Vec<u8>, DecryptionError> {
+        if bytes.len() < HEADER_LEN || !bytes.starts_with(&MAGIC) {
+            return Err(DecryptionError::NotEncrypted);
+        }
+
+        let (header, ciphertext) = bytes.split_at(HEADER_LEN);
+        let key_id = u32::from_be_bytes(header[MAGIC.len()..MAGIC.len() + 4].try_into().unwrap());
+        let nonce = XNonce::from_slice(&header[MAGIC.len() + 4..]);
+
+        let cipher = self.keys.get(&key_id)
+            .ok_or(DecryptionError::UnknownKey(key_id))?;
+        let aad = associated_data(&header[..MAGIC.len() + 4], key);
+
+        cipher.decrypt(nonce, Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| DecryptionError::AuthenticationFailed)
+    }
+}
+
+#[async_trait::async_trait]
+impl<C: Cache + Send + Sync> Cache for Encrypted<C> {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let key = key.to_key();
+        let bytes: Option<Vec<u8>> = self.inner.get(&key).await?;
+
+        bytes.map(|bytes| T::from_bytes(&self.decrypt(&key, &bytes)?))
+            .transpose()
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        let key = key.to_key();
+        let bytes = self.encrypt(&key, &value.to_bytes())?;
+        self.inner.set(key, bytes).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        self.inner.delete(key).await
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        self.inner.len().await
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        let key = key.to_key();
+        let bytes = self.encrypt(&key, &value.to_bytes())?;
+        self.inner.set_with_ttl(key, bytes, ttl).await
+    }
+}
+
+/// Associated data: the header (including the key id) and the key of the cache.
+fn associated_data(header: &[u8], key: &[u8]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(header.len() + key.len());
+    aad.extend_from_slice(header);
+    aad.extend_from_slice(key);
+    aad
+}
+
+/// Returned by [`Encrypted::get`] when a value can't be decrypted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecryptionError {
+    /// The value was not written by [`Encrypted`].
+    NotEncrypted,
+    /// The value was encrypted with a key which is not configured.
+    UnknownKey(u32),
+    /// The value was tampered with, moved from another key, or encrypted with another key.
+    AuthenticationFailed,
+}
+
+impl Display for DecryptionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecryptionError::NotEncrypted => write!(f, "value is not encrypted"),
+            DecryptionError::UnknownKey(key_id) => write!(f, "value is encrypted with unknown key {}", key_id),
+            DecryptionError::AuthenticationFailed => write!(f, "failed to authenticate encrypted value"),
+        }
+    }
+}
+
+impl std::error::Error for DecryptionError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::MemoryCache;
+    use super::*;
+
+    fn error(err: anyhow::Error) -> DecryptionError {
+        err.downcast::<DecryptionError>().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_encrypted() -> anyhow::Result<()> {
+        let inner = MemoryCache::default();
+        let cache = Encrypted::new(inner.clone(), 1, [7u8; 32]);
+
+        cache.set("user:1", String::from("jack")).await?;
+        cache.set(("user", 2), 42u64).await?;
+        assert_eq!(cache.get::<String>("user:1").await?, Some(String::from("jack")));
+        assert_eq!(cache.get::<u64>(("user", 2)).await?, Some(42));
+        assert_eq!(cache.get::<String>("none").await?, None);
+
+        let stored = inner.get::<Vec<u8>>("user:1").await?.unwrap();
+        assert!(!stored.windows(4).any(|window| window == b"jack"));
+
+        // the same value is encrypted with different nonces.
+        cache.set("user:3", String::from("jack")).await?;
+        assert_ne!(stored, inner.get::<Vec<u8>>("user:3").await?.unwrap());
+
+        cache.delete("user:1").await?;
+        assert_eq!(cache.len().await?, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_errors() -> anyhow::Result<()> {
+        let inner = MemoryCache::default();
+        let cache = Encrypted::new(inner.clone(), 1, [7u8; 32]);
+
+        inner.set("plain", String::from("jack")).await?;
+        assert_eq!(error(cache.get::<String>("plain").await.unwrap_err()), DecryptionError::NotEncrypted);
+
+        // moved to another key.
+        cache.set("a", String::from("jack")).await?;
+        let stored = inner.get::<Vec<u8>>("a").await?.unwrap();
+        inner.set("b", stored.clone()).await?;
+        assert_eq!(error(cache.get::<String>("b").await.unwrap_err()), DecryptionError::AuthenticationFailed);
+
+        // tampered.
+        let mut tampered = stored.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        inner.set("a", tampered).await?;
+        assert_eq!(error(cache.get::<String>("a").await.unwrap_err()), DecryptionError::AuthenticationFailed);
+
+        // encrypted with another key which has the same id.
+        inner.set("a", stored).await?;
+        let other = Encrypted::new(inner.clone(), 1, [8u8; 32]);
+        assert_eq!(error(other.get::<String>("a").await.unwrap_err()), DecryptionError::AuthenticationFailed);
+
+        let other = Encrypted::new(inner.clone(), 2, [8u8; 32]);
+        assert_eq!(error(other.get::<String>("a").await.unwrap_err()), DecryptionError::UnknownKey(1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_key_rotation() -> anyhow::Result<()> {
+        let inner = MemoryCache::default();
+        let old = Encrypted::new(inner.clone(), 1, [1u8; 32]);
+        old.set("a", String::from("old")).await?;
+
+        let new = Encrypted::new(inner.clone(), 2, [2u8; 32])
+            .decrypt_key(1, [1u8; 32]);
+        new.set("b", String::from("new")).await?;
+
+        assert_eq!(new.get::<String>("a").await?, Some(String::from("old")));
+        assert_eq!(new.get::<String>("b").await?, Some(String::from("new")));
+        assert_eq!(error(old.get::<String>("b").await.unwrap_err()), DecryptionError::UnknownKey(2));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,788 @@
+use std::fs::FileTimes;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use crate::{Cache, CacheKey, Cacheable, ValueReader};
+
+const MAGIC: [u8; 4] = [0xca, 0xa7, 0xf1, 0x1e];
+const VERSION: u8 = 1;
+
+/// magic, version, expiration, value length and key length.
+const FIXED_HEADER_LEN: usize = 4 + 1 + 8 + 8 + 4;
+const VALUE_LEN_OFFSET: u64 = 4 + 1 + 8;
+
+/// Keys longer than it are rejected, so that a corrupted header can't cause a huge allocation.
+const MAX_KEY_LEN: usize = 1 << 20;
+
+const TEMP_DIR: &str = "tmp";
+
+/// [`FileCache`] is a cache storing each entry as a file under a root directory.
+///
+/// Feature `file` must be enabled.
+///
+/// It's suitable for large values which should survive restarts,
+/// such as build artifacts and downloads.
+///
+/// ## Layout
+///
+/// An entry is stored at `{root}/{ab}/{cd}/{hash}`, where `hash` is the hex-encoded
+/// SHA-256 of the key, and `ab`, `cd` are its first bytes.
+/// Each file starts with a header containing the key, the size and the expiration time.
+///
+/// Files are written to `{root}/tmp` and renamed into place, so readers never see
+/// a partially written file, and several processes can share one root directory.
+/// A file truncated by a crash is detected by its size, and treated as a miss.
+/// Stale files are moved to `{root}/tmp` and checked again before they are removed,
+/// so an entry another process has just written is never removed by mistake.
+/// Keys must not be longer than 1 MiB.
+///
+/// ## Size Budget
+///
+/// With [`FileCacheBuilder::max_size`], the least recently accessed entries are removed
+/// when the total size of values exceeds the budget, until it's below 90% of the budget.
+/// Accesses are tracked by the access time of files, which is updated explicitly on `get`,
+/// so it works on `noatime` mounts too.
+///
+/// The total size is scanned when the cache is built, and tracked in memory afterwards.
+/// The write which exceeds the budget removes entries, while other writes go on.
+/// The removal scans the directory again, which also counts entries written by other processes.
+///
+/// ## Streaming
+///
+/// [`Cache::get_stream`] and [`Cache::set_stream`] read and write the files directly,
+/// without buffering values in memory.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache = FileCacheBuilder::new("/var/cache/artifacts")
+///     .max_size(10 * 1024 * 1024 * 1024)
+///     .finish()
+///     .await?;
+///
+/// cache.set_stream("model.bin", tokio::fs::File::open("model.bin").await?).await?;
+/// let reader = cache.get_stream("model.bin").await?.unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileCache {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    root: PathBuf,
+    max_size: Option<u64>,
+    /// The total size of values, tracked only with a budget.
+    used: AtomicU64,
+    /// Set while entries are removed to fit the budget.
+    evicting: AtomicBool,
+}
+
+/// The header of an entry file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Header {
+    /// Milliseconds since the unix epoch, 0 if it never expires.
+    expires_at: i64,
+    value_len: u64,
+    key: Vec<u8>,
+}
+
+impl Header {
+    fn len(&self) -> u64 {
+        (FIXED_HEADER_LEN + self.key.len()) as u64
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at != 0 && self.expires_at <= unix_millis(SystemTime::now())
+    }
+
+    /// Returns `true` if the entry is expired, or its file is truncated.
+    fn is_stale(&self, file_len: u64) -> bool {
+        self.is_expired() || self.len() + self.value_len != file_len
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(FIXED_HEADER_LEN + self.key.len());
+        ret.extend_from_slice(&MAGIC);
+        ret.push(VERSION);
+        ret.extend_from_slice(&self.expires_at.to_be_bytes());
+        ret.extend_from_slice(&self.value_len.to_be_bytes());
+        ret.extend_from_slice(&(self.key.len() as u32).to_be_bytes());
+        ret.extend_from_slice(&self.key);
+        ret
+    }
+
+    /// Decode the fixed part of a header, and return it with the length of the key.
+    fn decode_fixed(bytes: &[u8; FIXED_HEADER_LEN]) -> Option<(i64, u64, usize)> {
+        if bytes[..4] != MAGIC || bytes[4] != VERSION {
+            return None;
+        }
+
+        let expires_at = i64::from_be_bytes(bytes[5..13].try_into().unwrap());
+        let value_len = u64::from_be_bytes(bytes[13..21].try_into().unwrap());
+        let key_len = u32::from_be_bytes(bytes[21..25].try_into().unwrap());
+        Some((expires_at, value_len, key_len as usize))
+    }
+
+    /// Read a header from a file of `file_len` bytes. An invalid or truncated header is `None`.
+    async fn read_from<R: AsyncRead + Unpin>(reader: &mut R, file_len: u64) -> std::io::Result<Option<Self>> {
+        let mut fixed = [0u8; FIXED_HEADER_LEN];
+        if let Err(err) = reader.read_exact(&mut fixed).await {
+            return if err.kind() == ErrorKind::UnexpectedEof { Ok(None) } else { Err(err) };
+        }
+
+        let Some((expires_at, value_len, key_len)) = Self::decode_fixed(&fixed) else {
+            return Ok(None);
+        };
+        if key_len > MAX_KEY_LEN || (FIXED_HEADER_LEN + key_len) as u64 > file_len {
+            return Ok(None);
+        }
+
+        let mut key = vec![0u8; key_len];
+        if let Err(err) = reader.read_exact(&mut key).await {
+            return if err.kind() == ErrorKind::UnexpectedEof { Ok(None) } else { Err(err) };
+        }
+
+        Ok(Some(Self { expires_at, value_len, key }))
+    }
+}
+
+impl FileCache {
+    /// Get the root directory.
+    pub fn root(&self) -> &Path {
+        &self.inner.root
+    }
+
+    /// Delete expired entries, and return the number of deleted entries.
+    pub async fn purge_expired(&self) -> anyhow::Result<u64> {
+        let root = self.inner.root.clone();
+        let entries = blocking(move || scan(&root)).await?;
+
+        let mut purged = 0;
+        for entry in entries.into_iter().filter(|entry| entry.expired) {
+            if self.remove_stale(&entry.path).await? {
+                purged += 1;
+            }
+        }
+
+        Ok(purged)
+    }
+
+    fn path(&self, key: &[u8]) -> PathBuf {
+        let hash = hex::encode(Sha256::digest(key));
+        self.inner.root
+            .join(&hash[..2])
+            .join(&hash[2..4])
+            .join(hash)
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let name = format!(
+            "{:x}-{:x}-{:x}.tmp",
+            std::process::id(),
+            unix_millis(SystemTime::now()),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+        );
+        self.inner.root.join(TEMP_DIR).join(name)
+    }
+
+    /// Move the file at `path` out of place, and return the moved file with its length.
+    /// Returns `None` if there is no such file.
+    async fn take(&self, path: &Path) -> anyhow::Result<Option<(PathBuf, u64)>> {
+        let temp = self.temp_path();
+        tokio::fs::create_dir_all(temp.parent().unwrap()).await?;
+
+        match tokio::fs::rename(path, &temp).await {
+            Ok(()) => {},
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        let len = tokio::fs::metadata(&temp).await?.len();
+        Ok(Some((temp, len)))
+    }
+
+    /// Remove the entry at `path` if it's still stale, and return whether it was removed.
+    ///
+    /// Another process may have written a fresh entry to `path` meanwhile.
+    /// So the file is moved out of place before it's checked, and put back if it's fresh.
+    async fn remove_stale(&self, path: &Path) -> anyhow::Result<bool> {
+        let Some((temp, len)) = self.take(path).await? else {
+            return Ok(false);
+        };
+
+        let mut file = tokio::fs::File::open(&temp).await?;
+        let header = Header::read_from(&mut file, len).await?;
+        drop(file);
+
+        let stale = match &header {
+            Some(header) => header.is_stale(len),
+            None => true,
+        };
+        if stale {
+            // an invalid file is not counted in the total size.
+            let value_len = header.map_or(0, |header| len.saturating_sub(header.len()));
+            self.release(value_len);
+        }
+        if !stale {
+            // put it back, unless an even newer entry has been written.
+            match tokio::fs::hard_link(&temp, path).await {
+                Err(err) if err.kind() != ErrorKind::AlreadyExists => {
+                    let _ = tokio::fs::remove_file(&temp).await;
+                    return Err(err.into());
+                },
+                _ => {},
+            }
+        }
+        remove(&temp).await?;
+
+        Ok(stale)
+    }
+
+    /// Read the header of the entry of `key`, if it exists, is complete and is not expired.
+    /// A stale entry is removed.
+    async fn open(&self, key: &[u8]) -> anyhow::Result<Option<(tokio::fs::File, Header)>> {
+        let path = self.path(key);
+        let mut file = match tokio::fs::File::open(&path).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let file_len = file.metadata().await?.len();
+        let Some(header) = Header::read_from(&mut file, file_len).await? else {
+            return Ok(None);
+        };
+
+        if header.key != key {
+            // a hash collision.
+            return Ok(None);
+        }
+        if header.is_stale(file_len) {
+            drop(file);
+            self.remove_stale(&path).await?;
+            return Ok(None);
+        }
+
+        touch(path);
+        Ok(Some((file, header)))
+    }
+
+    /// Move a complete temp file into place.
+    async fn commit(&self, key: &[u8], temp: &Path, value_len: u64) -> anyhow::Result<()> {
+        let path = self.path(key);
+        tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+
+        let old_len = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => Some(metadata.len()),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        if let Err(err) = tokio::fs::rename(temp, &path).await {
+            let _ = tokio::fs::remove_file(temp).await;
+            return Err(err.into());
+        }
+
+        let header_len = (FIXED_HEADER_LEN + key.len()) as u64;
+        let old_value_len = old_len.map(|len| len.saturating_sub(header_len)).unwrap_or_default();
+        self.account(value_len, old_value_len).await
+    }
+
+    async fn write(&self, key: &[u8], value: &[u8], ttl: Option<Duration>) -> anyhow::Result<()> {
+        let header = Header {
+            expires_at: expires_at(ttl),
+            value_len: value.len() as u64,
+            key: key.to_vec(),
+        };
+
+        check_key(key)?;
+
+        let temp = self.temp_path();
+        let mut bytes = header.encode();
+        bytes.extend_from_slice(value);
+
+        let result = async {
+            let mut file = tokio::fs::File::create(&temp).await?;
+            file.write_all(&bytes).await?;
+            file.sync_all().await?;
+            anyhow::Ok(())
+        }.await;
+
+        if let Err(err) = result {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(err);
+        }
+
+        self.commit(key, &temp, header.value_len).await
+    }
+
+    /// Track the total size, and remove the least recently accessed entries if it exceeds the budget.
+    async fn account(&self, added: u64, removed: u64) -> anyhow::Result<()> {
+        let Some(max_size) = self.inner.max_size else {
+            return Ok(());
+        };
+
+        let total = update(&self.inner.used, |used| (used + added).saturating_sub(removed));
+        if total <= max_size {
+            return Ok(());
+        }
+
+        // only one write removes entries, the others don't wait for it.
+        if self.inner.evicting.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let result = self.evict(max_size).await;
+        self.inner.evicting.store(false, Ordering::Release);
+        result
+    }
+
+    /// Remove the least recently accessed entries, until the total size is below 90% of `max_size`.
+    async fn evict(&self, max_size: u64) -> anyhow::Result<()> {
+        // rescan, as other processes may share the directory.
+        let root = self.inner.root.clone();
+        let mut entries = blocking(move || scan(&root)).await?;
+        entries.sort_by_key(|entry| entry.accessed);
+
+        let target = max_size - max_size / 10;
+        let mut total: u64 = entries.iter().map(|entry| entry.value_len).sum();
+        for entry in entries {
+            if total <= target {
+                break;
+            }
+            remove(&entry.path).await?;
+            total -= entry.value_len;
+        }
+
+        self.inner.used.store(total, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Subtract the size of a removed value from the total size.
+    fn release(&self, value_len: u64) {
+        if self.inner.max_size.is_some() {
+            update(&self.inner.used, |used| used.saturating_sub(value_len));
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Cache for FileCache {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let key = key.to_key();
+        let Some((mut file, header)) = self.open(&key).await? else {
+            return Ok(None);
+        };
+
+        let mut value = Vec::with_capacity(header.value_len as usize);
+        file.read_to_end(&mut value).await?;
+
+        Ok(Some(T::from_bytes(&value)?))
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        self.write(&key.to_key(), &value.to_bytes(), None).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        let key = key.to_key();
+        // take the file first, so that the size of the removed entry is accounted,
+        // even if another process replaces it meanwhile.
+        let Some((temp, len)) = self.take(&self.path(&key)).await? else {
+            return Ok(());
+        };
+
+        remove(&temp).await?;
+        self.release(len.saturating_sub((FIXED_HEADER_LEN + key.len()) as u64));
+
+        Ok(())
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        let root = self.inner.root.clone();
+        let entries = blocking(move || scan(&root)).await?;
+
+        Ok(entries.iter().filter(|entry| !entry.expired).count())
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        self.write(&key.to_key(), &value.to_bytes(), Some(ttl)).await
+    }
+
+    async fn get_stream(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<ValueReader>> {
+        let key = key.to_key();
+        let Some((file, header)) = self.open(&key).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Box::new(file.take(header.value_len))))
+    }
+
+    async fn set_stream(&self, key: impl CacheKey + Send + Sync, mut reader: impl AsyncRead + Send + Unpin) -> anyhow::Result<()> {
+        let key = key.to_key();
+        check_key(&key)?;
+
+        let mut header = Header {
+            expires_at: 0,
+            value_len: 0,
+            key: key.clone(),
+        };
+
+        let temp = self.temp_path();
+        let result = async {
+            let mut file = tokio::fs::File::create(&temp).await?;
+            file.write_all(&header.encode()).await?;
+            header.value_len = tokio::io::copy(&mut reader, &mut file).await?;
+
+            // the length is known at the end of the stream.
+            file.seek(SeekFrom::Start(VALUE_LEN_OFFSET)).await?;
+            file.write_all(&header.value_len.to_be_bytes()).await?;
+            file.flush().await?;
+            file.sync_all().await?;
+            anyhow::Ok(())
+        }.await;
+
+        if let Err(err) = result {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(err);
+        }
+
+        self.commit(&key, &temp, header.value_len).await
+    }
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// The expiration time after `ttl`, or 0 (never expires) without a ttl or if it overflows.
+fn expires_at(ttl: Option<Duration>) -> i64 {
+    ttl.and_then(|ttl| i64::try_from(ttl.as_millis()).ok())
+        .and_then(|ttl| unix_millis(SystemTime::now()).checked_add(ttl))
+        .map_or(0, |expires_at| expires_at.max(1))
+}
+
+fn check_key(key: &[u8]) -> anyhow::Result<()> {
+    if key.len() > MAX_KEY_LEN {
+        anyhow::bail!("the key is too long: {} bytes, at most {} bytes", key.len(), MAX_KEY_LEN);
+    }
+    Ok(())
+}
+
+/// Update an atomic value, and return the new value.
+fn update(value: &AtomicU64, f: impl Fn(u64) -> u64) -> u64 {
+    let old = value.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| Some(f(value))).unwrap();
+    f(old)
+}
+
+/// Remove a file, which may have been removed by another process.
+async fn remove(path: &Path) -> anyhow::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Mark a file as accessed. Failures are ignored, as it only affects the cleanup order.
+fn touch(path: PathBuf) {
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::options().write(true).open(path)?;
+        file.set_times(FileTimes::new().set_accessed(SystemTime::now()))
+    });
+}
+
+async fn blocking<T, F>(f: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await?
+}
+
+/// An entry found by [`scan`].
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    value_len: u64,
+    accessed: SystemTime,
+    expired: bool,
+}
+
+/// List all entries under `root`. Temp files older than an hour (left by crashes) are removed.
+fn scan(root: &Path) -> anyhow::Result<Vec<Entry>> {
+    use std::io::Read;
+
+    let mut entries = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let read_dir = match std::fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        for item in read_dir {
+            let item = item?;
+            let path = item.path();
+            let Ok(metadata) = item.metadata() else {
+                continue;
+            };
+
+            if metadata.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            if dir.ends_with(TEMP_DIR) && dir.parent() == Some(root) {
+                let elapsed = metadata.modified().ok().and_then(|modified| modified.elapsed().ok());
+                let stale = matches!(elapsed, Some(elapsed) if elapsed > Duration::from_secs(3600));
+                if stale {
+                    let _ = std::fs::remove_file(&path);
+                }
+                continue;
+            }
+
+            let mut bytes = Vec::with_capacity(FIXED_HEADER_LEN);
+            let Ok(mut file) = std::fs::File::open(&path) else {
+                continue;
+            };
+            (&mut file).take(FIXED_HEADER_LEN as u64).read_to_end(&mut bytes)?;
+            let Some((expires_at, value_len, _)) = bytes.as_slice().try_into().ok().and_then(Header::decode_fixed) else {
+                continue;
+            };
+
+            entries.push(Entry {
+                path,
+                value_len,
+                accessed: metadata.accessed().or_else(|_| metadata.modified()).unwrap_or(UNIX_EPOCH),
+                expired: expires_at != 0 && expires_at <= unix_millis(SystemTime::now()),
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+/// [`FileCacheBuilder`] is used to build a [`FileCache`].
+#[derive(Debug, Clone)]
+pub struct FileCacheBuilder {
+    root: PathBuf,
+    max_size: Option<u64>,
+}
+
+impl FileCacheBuilder {
+    /// Create a new [`FileCacheBuilder`]. You need to specify the root directory.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            max_size: None,
+        }
+    }
+
+    /// Set the budget of the total size of values in bytes. It's unlimited by default.
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Finish and build a [`FileCache`]. The root directory is created if it doesn't exist.
+    ///
+    /// With a budget, the directory is scanned for the total size of existing entries.
+    pub async fn finish(self) -> anyhow::Result<FileCache> {
+        tokio::fs::create_dir_all(self.root.join(TEMP_DIR)).await?;
+
+        let used = match self.max_size {
+            Some(_) => {
+                let root = self.root.clone();
+                let entries = blocking(move || scan(&root)).await?;
+                entries.iter().map(|entry| entry.value_len).sum()
+            },
+            None => 0,
+        };
+
+        Ok(FileCache {
+            inner: Arc::new(Inner {
+                root: self.root,
+                max_size: self.max_size,
+                used: AtomicU64::new(used),
+                evicting: AtomicBool::new(false),
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("cache-any-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        root
+    }
+
+    #[tokio::test]
+    async fn test_file_cache() -> anyhow::Result<()> {
+        let root = temp_root("file");
+        let cache = FileCacheBuilder::new(&root).finish().await?;
+
+        cache.set("a", 1).await?;
+        cache.set("b", String::from("bbb")).await?;
+        cache.set(b"\xff\x00".as_slice(), vec![0u8, 1, 2, 255]).await?;
+
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+        assert_eq!(cache.get::<String>("b").await?, Some(String::from("bbb")));
+        assert_eq!(cache.get::<Vec<u8>>(b"\xff\x00".as_slice()).await?, Some(vec![0u8, 1, 2, 255]));
+        assert_eq!(cache.get::<u8>("none").await?, None);
+        assert_eq!(cache.len().await?, 3);
+
+        let path = cache.path(b"a");
+        assert!(path.starts_with(&root));
+        assert_eq!(path.strip_prefix(&root)?.components().count(), 3);
+
+        cache.set("b", String::from("overwritten")).await?;
+        assert_eq!(cache.get::<String>("b").await?, Some(String::from("overwritten")));
+
+        cache.delete("a").await?;
+        cache.delete("none").await?;
+        assert_eq!(cache.get::<u8>("a").await?, None);
+        assert_eq!(cache.len().await?, 2);
+
+        // survives restarts.
+        let cache = FileCacheBuilder::new(&root).finish().await?;
+        assert_eq!(cache.get::<String>("b").await?, Some(String::from("overwritten")));
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_cache_ttl_and_corruption() -> anyhow::Result<()> {
+        let root = temp_root("file-ttl");
+        let cache = FileCacheBuilder::new(&root).finish().await?;
+
+        cache.set_with_ttl("a", 1, Duration::from_millis(10)).await?;
+        cache.set_with_ttl("b", 2, Duration::MAX).await?;
+        cache.set("c", 3).await?;
+        assert_eq!(expires_at(Some(Duration::MAX)), 0);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(cache.len().await?, 2);
+        assert_eq!(cache.purge_expired().await?, 1);
+        assert_eq!(cache.get::<u8>("a").await?, None);
+        assert_eq!(cache.get::<u8>("b").await?, Some(2));
+
+        // truncated by a crash.
+        let path = cache.path(b"c");
+        let bytes = std::fs::read(&path)?;
+        std::fs::write(&path, &bytes[..bytes.len() - 1])?;
+        assert_eq!(cache.get::<u8>("c").await?, None);
+        assert!(!path.exists());
+
+        // a fresh entry is put back instead of removed.
+        cache.set("c", 3).await?;
+        assert!(!cache.remove_stale(&path).await?);
+        assert_eq!(cache.get::<u8>("c").await?, Some(3));
+        assert_eq!(std::fs::read_dir(root.join(TEMP_DIR))?.count(), 0);
+
+        assert!(cache.set(vec![0u8; MAX_KEY_LEN + 1], 1).await.is_err());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_cache_max_size() -> anyhow::Result<()> {
+        let root = temp_root("file-max-size");
+        let cache = FileCacheBuilder::new(&root)
+            .max_size(350)
+            .finish()
+            .await?;
+
+        cache.set("a", vec![1u8; 100]).await?;
+        cache.set("b", vec![2u8; 100]).await?;
+        cache.set("c", vec![3u8; 100]).await?;
+
+        // `a` is accessed more recently than `b`.
+        std::fs::File::options().write(true).open(cache.path(b"b"))?
+            .set_times(FileTimes::new().set_accessed(UNIX_EPOCH + Duration::from_secs(1)))?;
+        std::fs::File::options().write(true).open(cache.path(b"a"))?
+            .set_times(FileTimes::new().set_accessed(UNIX_EPOCH + Duration::from_secs(2)))?;
+
+        assert_eq!(cache.inner.used.load(Ordering::Relaxed), 300);
+
+        // removed until it's below 315 bytes.
+        cache.set("d", vec![4u8; 100]).await?;
+        assert_eq!(cache.len().await?, 3);
+        assert_eq!(cache.get::<Vec<u8>>("b").await?, None);
+        assert_eq!(cache.get::<Vec<u8>>("a").await?, Some(vec![1u8; 100]));
+        assert_eq!(cache.inner.used.load(Ordering::Relaxed), 300);
+
+        cache.delete("a").await?;
+        cache.set("c", vec![3u8; 50]).await?;
+        assert_eq!(cache.inner.used.load(Ordering::Relaxed), 150);
+
+        // the total size is scanned on start.
+        let cache = FileCacheBuilder::new(&root)
+            .max_size(350)
+            .finish()
+            .await?;
+        assert_eq!(cache.inner.used.load(Ordering::Relaxed), 150);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_cache_stream() -> anyhow::Result<()> {
+        let root = temp_root("file-stream");
+        let cache = FileCacheBuilder::new(&root).finish().await?;
+
+        let large: Vec<u8> = (0..1 << 20).map(|i| i as u8).collect();
+        cache.set_stream("large", &large[..]).await?;
+        assert_eq!(cache.get::<Vec<u8>>("large").await?, Some(large.clone()));
+
+        let mut bytes = Vec::new();
+        cache.get_stream("large").await?.unwrap().read_to_end(&mut bytes).await?;
+        assert_eq!(bytes, large);
+        assert!(cache.get_stream("none").await?.is_none());
+
+        assert_eq!(std::fs::read_dir(root.join(TEMP_DIR))?.count(), 0);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_cache_header() -> anyhow::Result<()> {
+        let header = Header {
+            expires_at: 42,
+            value_len: 7,
+            key: b"key".to_vec(),
+        };
+
+        let bytes = header.encode();
+        assert_eq!(bytes.len() as u64, header.len());
+        let len = bytes.len() as u64;
+        assert_eq!(Header::read_from(&mut &bytes[..], len).await?, Some(header.clone()));
+        assert_eq!(Header::read_from(&mut &bytes[..bytes.len() - 1], len - 1).await?, None);
+        assert_eq!(Header::read_from(&mut &b"not a header, but long enough"[..], 29).await?, None);
+
+        // a key length beyond the file is not allocated.
+        let mut corrupted = bytes.clone();
+        corrupted[21..25].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(Header::read_from(&mut &corrupted[..], len).await?, None);
+        assert_eq!(Header::read_from(&mut &bytes[..], len - 1).await?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use crate::Cacheable;
+
+/// Marks a value stored by [`ValueFormat::Binary`].
+/// It's never the first byte of a hex string.
+const BINARY_MARKER: u8 = 0x00;
+
+/// How remote backends (such as [`crate::RedisCache`] and [`crate::MySqlCache`]) store values.
+///
+/// Values are always readable regardless of the format they were written in,
+/// so the format can be switched on existing data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValueFormat {
+    /// Store [`Cacheable::to_hex`] strings. It's the default.
+    #[default]
+    Hex,
+    /// Store raw bytes, prefixed with a marker byte.
+    /// It halves the size of values, but the storage must be binary-safe
+    /// (for example, a `BLOB` column in mysql).
+    Binary,
+}
+
+impl ValueFormat {
+    pub(crate) fn encode<T: Cacheable>(self, value: &T) -> Vec<u8> {
+        match self {
+            ValueFormat::Hex => value.to_hex().into_bytes(),
+            ValueFormat::Binary => {
+                let bytes = value.to_bytes();
+                let mut ret = Vec::with_capacity(1 + bytes.len());
+                ret.push(BINARY_MARKER);
+                ret.extend_from_slice(&bytes);
+                ret
+            },
+        }
+    }
+
+    pub(crate) fn decode<T: Cacheable>(bytes: &[u8]) -> anyhow::Result<T> {
+        match bytes.split_first() {
+            Some((&BINARY_MARKER, rest)) => T::from_bytes(rest),
+            _ => T::from_hex(std::str::from_utf8(bytes)?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_format() -> anyhow::Result<()> {
+        let hex = ValueFormat::Hex.encode(&String::from("hello"));
+        assert_eq!(hex, b"68656c6c6f");
+        assert_eq!(ValueFormat::decode::<String>(&hex)?, "hello");
+
+        let binary = ValueFormat::Binary.encode(&String::from("hello"));
+        assert_eq!(binary, b"\x00hello");
+        assert_eq!(ValueFormat::decode::<String>(&binary)?, "hello");
+
+        assert_eq!(ValueFormat::decode::<()>(&ValueFormat::Hex.encode(&()))?, ());
+        assert_eq!(ValueFormat::decode::<()>(&ValueFormat::Binary.encode(&()))?, ());
+        assert_eq!(ValueFormat::decode::<u64>(&ValueFormat::Binary.encode(&42u64))?, 42);
+
+        assert!(ValueFormat::decode::<String>(b"not hex").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,574 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use crate::{Cache, CacheKey, Cacheable};
+
+/// Memcached treats expiration times longer than 30 days as unix timestamps.
+const MAX_RELATIVE_EXPIRATION: u64 = 30 * 24 * 3600;
+/// The maximum length of keys in the text protocol.
+const MAX_KEY_LEN: usize = 250;
+/// The prefix of keys stored as their hash.
+const HASHED_PREFIX: &str = "sha256:";
+/// The number of points of a server on the hash ring.
+const POINTS_PER_SERVER: usize = 160;
+
+/// [`MemcachedCache`] is a cache using memcached to store data.
+///
+/// It speaks the memcached text protocol over tokio, with one connection per server.
+/// Feature `memcached` must be enabled.
+///
+/// Keys are distributed over servers by consistent hashing, so adding or removing
+/// a server only moves a small part of the keys.
+/// Keys which are not valid memcached keys (longer than 250 bytes, or containing spaces
+/// or control characters) are stored as `sha256:` followed by the hex-encoded SHA-256 of the key.
+/// So are keys starting with `sha256:`, so that they can't collide with hashed keys.
+///
+/// Besides [`Cache`] (including [`Cache::set_with_ttl`]), it supports
+/// [`MemcachedCache::add`], [`MemcachedCache::gets`] and [`MemcachedCache::cas`],
+/// [`MemcachedCache::incr`] and [`MemcachedCache::decr`], and [`MemcachedCache::get_many`].
+///
+/// **Note**: [`Cache::len`] sums `curr_items` of all servers, which includes expired items
+/// not yet reclaimed, and items not written by this cache.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache = MemcachedCacheBuilder::new(["10.0.0.1:11211", "10.0.0.2:11211"])
+///     .timeout(Duration::from_secs(1))
+///     .finish();
+///
+/// cache.set_with_ttl("a", 1, Duration::from_secs(60)).await?;
+/// let a: Option<u8> = cache.get("a").await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemcachedCache {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    servers: Vec<Server>,
+    /// Points of the hash ring, sorted by hash.
+    ring: Vec<(u32, usize)>,
+}
+
+#[derive(Debug)]
+struct Server {
+    addr: String,
+    timeout: Duration,
+    conn: Mutex<Option<BufStream<TcpStream>>>,
+}
+
+/// A value returned by a retrieval command.
+#[derive(Debug)]
+struct Item {
+    key: String,
+    cas: Option<u64>,
+    data: Vec<u8>,
+}
+
+/// A response to a command: the returned items (or stats), and the status line.
+#[derive(Debug, Default)]
+struct Reply {
+    items: Vec<Item>,
+    stats: Vec<(String, String)>,
+    status: String,
+}
+
+impl Server {
+    async fn execute(&self, command: &[u8]) -> anyhow::Result<Reply> {
+        let mut conn = self.conn.lock().await;
+
+        let result = tokio::time::timeout(self.timeout, async {
+            if conn.is_none() {
+                *conn = Some(BufStream::new(TcpStream::connect(&self.addr).await?));
+            }
+
+            let stream = conn.as_mut().unwrap();
+            stream.write_all(command).await?;
+            stream.flush().await?;
+            read_reply(stream).await
+        }).await;
+
+        let result = match result {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("memcached {} timed out", self.addr)),
+        };
+
+        // the state of the connection is unknown after an error.
+        if result.is_err() {
+            *conn = None;
+        }
+
+        let reply = result?;
+        if reply.status == "ERROR" || reply.status.starts_with("CLIENT_ERROR") || reply.status.starts_with("SERVER_ERROR") {
+            anyhow::bail!("memcached {}: {}", self.addr, reply.status);
+        }
+
+        Ok(reply)
+    }
+}
+
+async fn read_reply(stream: &mut BufStream<TcpStream>) -> anyhow::Result<Reply> {
+    let mut reply = Reply::default();
+
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            anyhow::bail!("memcached closed the connection");
+        }
+        let line = line.trim_end_matches("\r\n");
+
+        if let Some(rest) = line.strip_prefix("VALUE ") {
+            // VALUE <key> <flags> <bytes> [<cas>]
+            let parts: Vec<&str> = rest.split(' ').collect();
+            let (Some(key), Some(len)) = (parts.first(), parts.get(2)) else {
+                anyhow::bail!("invalid memcached response: {}", line);
+            };
+
+            let mut data = vec![0u8; len.parse::<usize>()? + 2];
+            stream.read_exact(&mut data).await?;
+            data.truncate(data.len() - 2);
+
+            reply.items.push(Item {
+                key: key.to_string(),
+                cas: parts.get(3).map(|cas| cas.parse()).transpose()?,
+                data,
+            });
+        } else if let Some(rest) = line.strip_prefix("STAT ") {
+            let (name, value) = rest.split_once(' ').unwrap_or((rest, ""));
+            reply.stats.push((name.to_string(), value.to_string()));
+        } else {
+            reply.status = line.to_string();
+            return Ok(reply);
+        }
+    }
+}
+
+impl MemcachedCache {
+    /// Store a value only if the key doesn't exist. Returns whether it's stored.
+    pub async fn add<T: Cacheable>(&self, key: impl CacheKey, value: T, ttl: Option<Duration>) -> anyhow::Result<bool> {
+        let reply = self.store("add", &key.to_key(), &value.to_bytes(), ttl, None).await?;
+        Ok(reply.status == "STORED")
+    }
+
+    /// Get a value with its CAS token, which is used by [`MemcachedCache::cas`].
+    pub async fn gets<T: Cacheable>(&self, key: impl CacheKey) -> anyhow::Result<Option<(T, u64)>> {
+        let key = memcached_key(&key.to_key());
+        let server = self.server(&key);
+        let reply = server.execute(format!("gets {}\r\n", key).as_bytes()).await?;
+
+        let Some(item) = reply.items.into_iter().next() else {
+            return Ok(None);
+        };
+        let cas = item.cas.ok_or_else(|| anyhow::anyhow!("memcached returned no cas token"))?;
+
+        Ok(Some((T::from_bytes(&item.data)?, cas)))
+    }
+
+    /// Store a value only if it's not modified since it was read by [`MemcachedCache::gets`].
+    /// Returns whether it's stored. It's not stored if the value is modified or deleted.
+    pub async fn cas<T: Cacheable>(&self, key: impl CacheKey, value: T, cas: u64, ttl: Option<Duration>) -> anyhow::Result<bool> {
+        let reply = self.store("cas", &key.to_key(), &value.to_bytes(), ttl, Some(cas)).await?;
+        Ok(reply.status == "STORED")
+    }
+
+    /// Increase a counter, and return the new value, or `None` if the key doesn't exist.
+    ///
+    /// Counters are stored as decimal strings. Create them by storing a `String`,
+    /// for example `cache.add("hits", String::from("0"), None)`, and read them as `String`.
+    pub async fn incr(&self, key: impl CacheKey, delta: u64) -> anyhow::Result<Option<u64>> {
+        self.arithmetic("incr", &key.to_key(), delta).await
+    }
+
+    /// Decrease a counter, and return the new value, or `None` if the key doesn't exist.
+    /// Memcached doesn't decrease counters below 0.
+    pub async fn decr(&self, key: impl CacheKey, delta: u64) -> anyhow::Result<Option<u64>> {
+        self.arithmetic("decr", &key.to_key(), delta).await
+    }
+
+    /// Get several values, with one request per server.
+    /// The result has the same order as `keys`.
+    pub async fn get_many<K: CacheKey, T: Cacheable>(&self, keys: impl IntoIterator<Item = K>) -> anyhow::Result<Vec<Option<T>>> {
+        let keys: Vec<String> = keys.into_iter().map(|key| memcached_key(&key.to_key())).collect();
+
+        let mut by_server: HashMap<usize, Vec<&str>> = HashMap::new();
+        for key in &keys {
+            by_server.entry(self.server_index(key)).or_default().push(key);
+        }
+
+        let mut values = HashMap::new();
+        for (index, server_keys) in by_server {
+            let command = format!("get {}\r\n", server_keys.join(" "));
+            let reply = self.inner.servers[index].execute(command.as_bytes()).await?;
+            values.extend(reply.items.into_iter().map(|item| (item.key, item.data)));
+        }
+
+        keys.iter()
+            .map(|key| values.get(key).map(|data| T::from_bytes(data)).transpose())
+            .collect()
+    }
+
+    async fn store(&self, command: &str, key: &[u8], value: &[u8], ttl: Option<Duration>, cas: Option<u64>) -> anyhow::Result<Reply> {
+        let key = memcached_key(key);
+        let server = self.server(&key);
+
+        let mut request = format!("{} {} 0 {} {}", command, key, expiration(ttl), value.len()).into_bytes();
+        if let Some(cas) = cas {
+            request.extend_from_slice(format!(" {}", cas).as_bytes());
+        }
+        request.extend_from_slice(b"\r\n");
+        request.extend_from_slice(value);
+        request.extend_from_slice(b"\r\n");
+
+        server.execute(&request).await
+    }
+
+    async fn arithmetic(&self, command: &str, key: &[u8], delta: u64) -> anyhow::Result<Option<u64>> {
+        let key = memcached_key(key);
+        let server = self.server(&key);
+        let reply = server.execute(format!("{} {} {}\r\n", command, key, delta).as_bytes()).await?;
+
+        match reply.status.as_str() {
+            "NOT_FOUND" => Ok(None),
+            status => Ok(Some(status.parse()?)),
+        }
+    }
+
+    fn server(&self, key: &str) -> &Server {
+        &self.inner.servers[self.server_index(key)]
+    }
+
+    fn server_index(&self, key: &str) -> usize {
+        let hash = ring_hash(key.as_bytes());
+        let ring = &self.inner.ring;
+        let point = ring.partition_point(|(point, _)| *point < hash);
+        ring[point % ring.len()].1
+    }
+}
+
+#[async_trait::async_trait]
+impl Cache for MemcachedCache {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let key = memcached_key(&key.to_key());
+        let server = self.server(&key);
+        let reply = server.execute(format!("get {}\r\n", key).as_bytes()).await?;
+
+        reply.items.into_iter()
+            .next()
+            .map(|item| T::from_bytes(&item.data))
+            .transpose()
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        self.store("set", &key.to_key(), &value.to_bytes(), None, None).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        let key = memcached_key(&key.to_key());
+        let server = self.server(&key);
+        server.execute(format!("delete {}\r\n", key).as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        let mut len = 0;
+        for server in &self.inner.servers {
+            let reply = server.execute(b"stats\r\n").await?;
+            let items = reply.stats.iter()
+                .find(|(name, _)| name == "curr_items")
+                .ok_or_else(|| anyhow::anyhow!("memcached {} returned no curr_items", server.addr))?;
+            len += items.1.parse::<usize>()?;
+        }
+
+        Ok(len)
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        self.store("set", &key.to_key(), &value.to_bytes(), Some(ttl), None).await?;
+        Ok(())
+    }
+}
+
+/// Convert a key to a valid memcached key.
+fn memcached_key(key: &[u8]) -> String {
+    let valid = !key.is_empty() && key.len() <= MAX_KEY_LEN && key.iter().all(|byte| byte.is_ascii_graphic());
+    if valid && !key.starts_with(HASHED_PREFIX.as_bytes()) {
+        return String::from_utf8(key.to_vec()).unwrap();
+    }
+
+    format!("{}{}", HASHED_PREFIX, hex::encode(Sha256::digest(key)))
+}
+
+/// The expiration time in seconds, rounded up. 0 means never.
+///
+/// Memcached reads it as a signed 32-bit integer, so a later expiration time never expires.
+fn expiration(ttl: Option<Duration>) -> u64 {
+    let Some(ttl) = ttl else {
+        return 0;
+    };
+
+    let secs = ttl.as_secs().saturating_add((ttl.subsec_nanos() > 0 || ttl.is_zero()) as u64);
+    if secs <= MAX_RELATIVE_EXPIRATION {
+        return secs;
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    match now.checked_add(secs) {
+        Some(expires_at) if expires_at <= i32::MAX as u64 => expires_at,
+        _ => 0,
+    }
+}
+
+fn ring_hash(bytes: &[u8]) -> u32 {
+    let hash = Sha256::digest(bytes);
+    u32::from_be_bytes(hash[..4].try_into().unwrap())
+}
+
+/// [`MemcachedCacheBuilder`] is used to build a [`MemcachedCache`].
+#[derive(Debug, Clone)]
+pub struct MemcachedCacheBuilder {
+    servers: Vec<String>,
+    timeout: Duration,
+}
+
+impl MemcachedCacheBuilder {
+    /// Create a new [`MemcachedCacheBuilder`]. You need to specify the addresses of servers.
+    pub fn new<S: ToString>(servers: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            servers: servers.into_iter().map(|server| server.to_string()).collect(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Set the timeout of each request, including connecting. It's 5 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Finish and build a [`MemcachedCache`]. Connections are opened when they are used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no server is specified.
+    pub fn finish(self) -> MemcachedCache {
+        assert!(!self.servers.is_empty(), "at least one memcached server is required");
+
+        let mut ring = Vec::with_capacity(self.servers.len() * POINTS_PER_SERVER);
+        for (index, server) in self.servers.iter().enumerate() {
+            for point in 0..POINTS_PER_SERVER {
+                ring.push((ring_hash(format!("{}-{}", server, point).as_bytes()), index));
+            }
+        }
+        ring.sort_unstable();
+
+        let servers = self.servers.into_iter()
+            .map(|addr| Server {
+                addr,
+                timeout: self.timeout,
+                conn: Mutex::new(None),
+            })
+            .collect();
+
+        MemcachedCache {
+            inner: Arc::new(Inner { servers, ring }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use super::*;
+
+    type Items = Arc<std::sync::Mutex<HashMap<String, (Vec<u8>, u64)>>>;
+
+    /// A minimal memcached server, which ignores expiration times.
+    async fn fake_memcached() -> anyhow::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?.to_string();
+        let items: Items = Default::default();
+
+        tokio::spawn(async move {
+            let mut next_cas = 0;
+            while let Ok((stream, _)) = listener.accept().await {
+                let items = items.clone();
+                let mut stream = BufStream::new(stream);
+                next_cas += 1000;
+                let mut next_cas = next_cas;
+
+                tokio::spawn(async move {
+                    let mut line = String::new();
+                    while stream.read_line(&mut line).await.unwrap_or(0) > 0 {
+                        let parts: Vec<String> = line.split_whitespace().map(String::from).collect();
+                        line.clear();
+
+                        let reply = match parts[0].as_str() {
+                            "get" | "gets" => {
+                                let items = items.lock().unwrap();
+                                let mut reply = Vec::new();
+                                for key in &parts[1..] {
+                                    if let Some((data, cas)) = items.get(key) {
+                                        let cas = if parts[0] == "gets" { format!(" {}", cas) } else { String::new() };
+                                        reply.extend_from_slice(format!("VALUE {} 0 {}{}\r\n", key, data.len(), cas).as_bytes());
+                                        reply.extend_from_slice(data);
+                                        reply.extend_from_slice(b"\r\n");
+                                    }
+                                }
+                                reply.extend_from_slice(b"END\r\n");
+                                reply
+                            },
+                            "set" | "add" | "cas" => {
+                                let mut data = vec![0u8; parts[4].parse::<usize>().unwrap() + 2];
+                                stream.read_exact(&mut data).await.unwrap();
+                                data.truncate(data.len() - 2);
+
+                                next_cas += 1;
+                                let mut items = items.lock().unwrap();
+                                let status = match (parts[0].as_str(), items.get(&parts[1])) {
+                                    ("add", Some(_)) => "NOT_STORED",
+                                    ("cas", None) => "NOT_FOUND",
+                                    ("cas", Some((_, cas))) if cas.to_string() != parts[5] => "EXISTS",
+                                    _ => {
+                                        items.insert(parts[1].clone(), (data, next_cas));
+                                        "STORED"
+                                    },
+                                };
+                                format!("{}\r\n", status).into_bytes()
+                            },
+                            "delete" => match items.lock().unwrap().remove(&parts[1]) {
+                                Some(_) => b"DELETED\r\n".to_vec(),
+                                None => b"NOT_FOUND\r\n".to_vec(),
+                            },
+                            "incr" | "decr" => match items.lock().unwrap().get_mut(&parts[1]) {
+                                Some((data, _)) => {
+                                    let value: u64 = String::from_utf8_lossy(data).parse().unwrap();
+                                    let delta: u64 = parts[2].parse().unwrap();
+                                    let value = if parts[0] == "incr" { value + delta } else { value.saturating_sub(delta) };
+                                    *data = value.to_string().into_bytes();
+                                    format!("{}\r\n", value).into_bytes()
+                                },
+                                None => b"NOT_FOUND\r\n".to_vec(),
+                            },
+                            "stats" => format!("STAT pid 1\r\nSTAT curr_items {}\r\nEND\r\n", items.lock().unwrap().len()).into_bytes(),
+                            _ => b"ERROR\r\n".to_vec(),
+                        };
+
+                        if stream.write_all(&reply).await.is_err() || stream.flush().await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        Ok(addr)
+    }
+
+    #[tokio::test]
+    async fn test_memcached_cache_fake() -> anyhow::Result<()> {
+        let servers = vec![fake_memcached().await?, fake_memcached().await?, fake_memcached().await?];
+        let cache = MemcachedCacheBuilder::new(&servers).finish();
+
+        for i in 0..30u32 {
+            cache.set(("key", i), i).await?;
+        }
+        cache.set(b"binary key \xff".as_slice(), String::from("binary")).await?;
+        cache.set_with_ttl("ttl", 1u8, Duration::from_secs(60)).await?;
+
+        assert_eq!(cache.get::<u32>(("key", 7)).await?, Some(7));
+        assert_eq!(cache.get::<String>(b"binary key \xff".as_slice()).await?, Some(String::from("binary")));
+        assert_eq!(cache.get::<u8>("none").await?, None);
+        assert_eq!(cache.len().await?, 32);
+
+        // keys are spread over all servers.
+        let used: std::collections::HashSet<usize> = (0..30u32)
+            .map(|i| cache.server_index(&memcached_key(&("key", i).to_key())))
+            .collect();
+        assert_eq!(used.len(), 3);
+
+        let values: Vec<Option<u32>> = cache.get_many([("key", 3), ("key", 100), ("key", 20)]).await?;
+        assert_eq!(values, vec![Some(3), None, Some(20)]);
+
+        cache.delete(("key", 7)).await?;
+        cache.delete("none").await?;
+        assert_eq!(cache.get::<u32>(("key", 7)).await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memcached_cache_fake_atomic() -> anyhow::Result<()> {
+        let cache = MemcachedCacheBuilder::new([fake_memcached().await?]).finish();
+
+        assert!(cache.add("a", 1u8, None).await?);
+        assert!(!cache.add("a", 2u8, None).await?);
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+
+        let (value, cas) = cache.gets::<u8>("a").await?.unwrap();
+        assert_eq!(value, 1);
+        assert!(cache.cas("a", 3u8, cas, None).await?);
+        assert!(!cache.cas("a", 4u8, cas, None).await?);
+        assert!(!cache.cas("none", 4u8, cas, None).await?);
+        assert_eq!(cache.get::<u8>("a").await?, Some(3));
+        assert_eq!(cache.gets::<u8>("none").await?, None);
+
+        assert_eq!(cache.incr("hits", 1).await?, None);
+        cache.add("hits", String::from("10"), None).await?;
+        assert_eq!(cache.incr("hits", 5).await?, Some(15));
+        assert_eq!(cache.decr("hits", 20).await?, Some(0));
+        assert_eq!(cache.get::<String>("hits").await?, Some(String::from("0")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memcached_helpers() {
+        assert_eq!(memcached_key(b"user:1"), "user:1");
+        assert_eq!(memcached_key(b"with space"), format!("sha256:{}", hex::encode(Sha256::digest(b"with space"))));
+        assert!(memcached_key("k".repeat(251).as_bytes()).starts_with("sha256:"));
+        assert!(memcached_key(b"").starts_with("sha256:"));
+        let hashed = memcached_key(b"with space");
+        assert_ne!(memcached_key(hashed.as_bytes()), hashed);
+
+        assert_eq!(expiration(None), 0);
+        assert_eq!(expiration(Some(Duration::from_secs(60))), 60);
+        assert_eq!(expiration(Some(Duration::from_millis(1500))), 2);
+        assert_eq!(expiration(Some(Duration::ZERO)), 1);
+        assert!(expiration(Some(Duration::from_secs(MAX_RELATIVE_EXPIRATION + 1))) > MAX_RELATIVE_EXPIRATION * 10);
+        assert_eq!(expiration(Some(Duration::from_secs(u32::MAX as u64))), 0);
+        assert_eq!(expiration(Some(Duration::MAX)), 0);
+    }
+
+    #[tokio::test]
+    async fn test_memcached_cache() -> anyhow::Result<()> {
+        let cache = MemcachedCacheBuilder::new(["127.0.0.1:11211"]).finish();
+
+        cache.set("user_id", 114514).await?;
+        cache.set_with_ttl("username", String::from("jack"), Duration::from_secs(1)).await?;
+
+        assert_eq!(cache.get::<usize>("user_id").await?, Some(114514));
+        assert_eq!(cache.get::<String>("username").await?, Some(String::from("jack")));
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(cache.get::<String>("username").await?, None);
+
+        cache.delete("user_id").await?;
+        assert_eq!(cache.get::<usize>("user_id").await?, None);
+
+        // the server may hold keys of other clients.
+        cache.set("len", 1).await?;
+        let len = cache.len().await?;
+        assert!(len >= 1);
+        cache.delete("len").await?;
+        assert_eq!(cache.len().await?, len - 1);
+
+        Ok(())
+    }
+}
@@ -1,16 +1,22 @@
-use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use crate::Cacheable;
-use crate::Cache;
+use crate::{Cache, CacheKey, Cacheable};
 
 /// Cache using memory.
 /// 
 /// Data is stored in memory. However, this cache will serialize and deserialize data,
 /// so it may not be so efficient.
 /// 
+/// If feature `bytes` is enabled, values are stored as [`bytes::Bytes`].
+/// Storing and retrieving [`bytes::Bytes`] (or storing `Vec<u8>` and [`String`])
+/// then doesn't copy the data, which suits large blobs.
+/// 
+/// Values set by [`Cache::set_with_ttl`] are removed when they are read after expiring,
+/// or by [`MemoryCache::purge_expired`]. A ttl too large to represent never expires.
+/// 
 /// [`MemoryCache`] implements [`Cache`]. See [`Cache`] for more details.
 /// 
 /// ## Example
@@ -43,17 +49,53 @@ impl MemoryCache
         Self {
             inner: Arc::new(RwLock::new(Inner {
                 map: HashMap::with_capacity(cap),
+                expiries: HashMap::new(),
             }))
         }
     }
+
+    /// Remove all expired entries, and return the number of removed entries.
+    pub async fn purge_expired(&self) -> anyhow::Result<u64> {
+        let mut inner = self.inner.write().await;
+        let now = Instant::now();
+
+        let expired: Vec<Vec<u8>> = inner.expiries.iter()
+            .filter(|(_, &expires_at)| expires_at <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired {
+            inner.remove(key);
+        }
+
+        Ok(expired.len() as u64)
+    }
 }
 
 #[async_trait::async_trait]
 impl Cache for MemoryCache
 {
-    async fn get<T: Cacheable + Send + Sync>(&self, key: &str) -> anyhow::Result<Option<T>> {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let key = key.to_key();
         let inner = self.inner.read().await;
-        let ret = inner.get(key.as_bytes())
+        if inner.is_expired(&key, Instant::now()) {
+            drop(inner);
+
+            let mut inner = self.inner.write().await;
+            // it may have been set again in the meantime.
+            if inner.is_expired(&key, Instant::now()) {
+                inner.remove(&key);
+            }
+            return Ok(None);
+        }
+
+        #[cfg(feature = "bytes")]
+        let ret = inner.map.get(&key)
+            .cloned()
+            .map(T::from_buf)
+            .transpose()?;
+
+        #[cfg(not(feature = "bytes"))]
+        let ret = inner.map.get(&key)
             .map(|val| val.as_slice())
             .map(T::from_bytes)
             .transpose()?;
@@ -61,46 +103,78 @@ impl Cache for MemoryCache
         Ok(ret)
     }
 
-    async fn set<T: Cacheable + Send + Sync>(&self, key: &str, value: T) -> anyhow::Result<()> {
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        #[cfg(feature = "bytes")]
+        let bytes = value.into_buf();
+        #[cfg(not(feature = "bytes"))]
         let bytes = value.to_bytes();
 
         let mut inner = self.inner.write().await;
-        inner.insert(key.as_bytes().to_vec(), bytes);
+        inner.insert(key.to_key(), bytes, None);
 
         Ok(())
     }
 
-    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
         let mut inner = self.inner.write().await;
-        inner.remove(key.as_bytes());
+        inner.remove(&key.to_key());
 
         Ok(())
     }
 
     async fn len(&self) -> anyhow::Result<usize> {
         let inner = self.inner.read().await;
-        Ok(inner.len())
+        let now = Instant::now();
+        let expired = inner.expiries.values()
+            .filter(|&&expires_at| expires_at <= now)
+            .count();
+
+        Ok(inner.map.len() - expired)
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        #[cfg(feature = "bytes")]
+        let bytes = value.into_buf();
+        #[cfg(not(feature = "bytes"))]
+        let bytes = value.to_bytes();
+
+        let mut inner = self.inner.write().await;
+        // a ttl too large for `Instant` never expires.
+        inner.insert(key.to_key(), bytes, Instant::now().checked_add(ttl));
+
+        Ok(())
     }
 }
 
+#[cfg(feature = "bytes")]
+type Value = bytes::Bytes;
+#[cfg(not(feature = "bytes"))]
+type Value = Vec<u8>;
+
 #[derive(Debug)]
 struct Inner {
-    map: HashMap<Vec<u8>, Vec<u8>>,
+    map: HashMap<Vec<u8>, Value>,
+    /// Expiration times of the entries set with a ttl.
+    expiries: HashMap<Vec<u8>, Instant>,
 }
 
-impl Deref for Inner
+impl Inner
 {
-    type Target = HashMap<Vec<u8>, Vec<u8>>;
+    fn insert(&mut self, key: Vec<u8>, value: Value, expires_at: Option<Instant>) {
+        match expires_at {
+            Some(expires_at) => self.expiries.insert(key.clone(), expires_at),
+            None => self.expiries.remove(&key),
+        };
+        self.map.insert(key, value);
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.map
+    fn remove(&mut self, key: &[u8]) {
+        self.expiries.remove(key);
+        self.map.remove(key);
     }
-}
 
-impl DerefMut for Inner
-{
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.map   
+    fn is_expired(&self, key: &[u8], now: Instant) -> bool {
+        self.expiries.get(key).is_some_and(|&expires_at| expires_at <= now)
     }
 }
 
@@ -135,4 +209,97 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_memory_cache_stream() -> anyhow::Result<()> {
+        use tokio::io::AsyncReadExt;
+
+        let cache = MemoryCache::default();
+
+        cache.set_stream("a", &b"hello"[..]).await?;
+        assert_eq!(cache.get::<Vec<u8>>("a").await?, Some(b"hello".to_vec()));
+
+        let mut bytes = Vec::new();
+        cache.get_stream("a").await?.unwrap().read_to_end(&mut bytes).await?;
+        assert_eq!(bytes, b"hello");
+        assert!(cache.get_stream("none").await?.is_none());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "bytes")]
+    #[tokio::test]
+    async fn test_memory_cache_bytes() -> anyhow::Result<()> {
+        let cache = MemoryCache::default();
+
+        let blob = bytes::Bytes::from(vec![7u8; 1 << 20]);
+        cache.set("blob", blob.clone()).await?;
+
+        let a: bytes::Bytes = cache.get("blob").await?.unwrap();
+        let b: bytes::Bytes = cache.get("blob").await?.unwrap();
+        assert_eq!(a, blob);
+        assert_eq!(a.as_ptr(), blob.as_ptr());
+        assert_eq!(b.as_ptr(), blob.as_ptr());
+
+        assert_eq!(cache.get::<Vec<u8>>("blob").await?.unwrap(), vec![7u8; 1 << 20]);
+
+        cache.set("n", 1u8).await?;
+        assert_eq!(cache.get::<u16>("n").await?, Some(1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_keys() -> anyhow::Result<()> {
+        let cache = MemoryCache::default();
+
+        cache.set(("user", 42), String::from("jack")).await?;
+        cache.set(42, 1).await?;
+        cache.set(vec![0u8, 255], 2).await?;
+        cache.set([0xffu8; 16], 3).await?;
+
+        assert_eq!(cache.get::<String>(("user", 42)).await?, Some(String::from("jack")));
+        assert_eq!(cache.get::<String>("user:42").await?, None);
+        assert_eq!(cache.get::<u8>(42u64).await?, Some(1));
+        assert_eq!(cache.get::<u8>(String::from("42")).await?, None);
+        assert_eq!(cache.get::<u8>([0u8, 255].as_slice()).await?, Some(2));
+        assert_eq!(cache.get::<u8>(&[0xffu8; 16]).await?, Some(3));
+        assert_eq!(cache.get::<u8>(("user:", 42)).await?, None);
+
+        cache.delete(vec![0u8, 255]).await?;
+        assert_eq!(cache.len().await?, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_ttl() -> anyhow::Result<()> {
+        let cache = MemoryCache::default();
+
+        cache.set_with_ttl("a", 1, Duration::from_millis(10)).await?;
+        cache.set_with_ttl("b", 2, Duration::from_secs(60)).await?;
+        cache.set_with_ttl("c", 3, Duration::from_millis(10)).await?;
+        cache.set("d", 4).await?;
+        // overwriting a value clears its ttl.
+        cache.set("c", 3).await?;
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+        assert_eq!(cache.len().await?, 4);
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.len().await?, 3);
+        assert_eq!(cache.get::<u8>("a").await?, None);
+        assert_eq!(cache.get::<u8>("b").await?, Some(2));
+        assert_eq!(cache.get::<u8>("c").await?, Some(3));
+        assert_eq!(cache.inner.read().await.map.len(), 3);
+
+        cache.set_with_ttl("e", 5, Duration::from_millis(1)).await?;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(cache.purge_expired().await?, 1);
+        assert_eq!(cache.inner.read().await.expiries.len(), 1);
+
+        cache.set_with_ttl("f", 6, Duration::MAX).await?;
+        assert_eq!(cache.get::<u8>("f").await?, Some(6));
+
+        Ok(())
+    }
 }
@@ -1,6 +1,17 @@
 mod memory;
 pub use memory::*;
 
+mod object;
+pub use object::*;
+
+mod noop;
+pub use noop::*;
+
+#[cfg(any(feature = "redis", feature = "mysql"))]
+mod format;
+#[cfg(any(feature = "redis", feature = "mysql"))]
+pub use format::*;
+
 #[cfg(feature = "redis")]
 mod redis;
 #[cfg(feature = "redis")]
@@ -11,17 +22,119 @@ mod mysql;
 #[cfg(feature = "mysql")]
 pub use mysql::*;
 
-use crate::Cacheable;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::*;
+
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "postgres")]
+pub use postgres::*;
+#[cfg(feature = "sql")]
+mod sql;
+#[cfg(feature = "sql")]
+pub use sql::*;
+
+#[cfg(feature = "file")]
+mod file;
+#[cfg(feature = "file")]
+pub use file::*;
+
+#[cfg(feature = "bitcask")]
+mod bitcask;
+#[cfg(feature = "bitcask")]
+pub use bitcask::*;
+
+#[cfg(feature = "redb")]
+mod redb;
+#[cfg(feature = "redb")]
+pub use redb::*;
+#[cfg(feature = "memcached")]
+mod memcached;
+#[cfg(feature = "memcached")]
+pub use memcached::*;
+#[cfg(feature = "object_store")]
+mod object_store;
+#[cfg(feature = "object_store")]
+pub use self::object_store::*;
+
+mod tagged;
+pub use tagged::*;
+
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+mod compressed;
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+pub use compressed::*;
+
+#[cfg(feature = "encryption")]
+mod encrypted;
+#[cfg(feature = "encryption")]
+pub use encrypted::*;
+
+#[cfg(feature = "integrity")]
+mod checked;
+#[cfg(feature = "integrity")]
+pub use checked::*;
+
+mod chunked;
+pub use chunked::*;
+
+mod switchable;
+pub use switchable::*;
+
+mod typed;
+pub use typed::*;
+
+use std::io::Cursor;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use crate::{CacheKey, Cacheable};
+
+/// A reader of a value, returned by [`Cache::get_stream`].
+pub type ValueReader = Box<dyn AsyncRead + Send + Unpin>;
 
 /// A cache trait.
 /// 
 /// It describes the basic operations of a cache.
 /// All functions are async, because we may use async storage backends.
+///
+/// Keys can be any [`CacheKey`], for example `&str`, integers or tuples.
+///
+/// ## Streaming
+///
+/// Large values can be read and written as streams of raw bytes by
+/// [`Cache::get_stream`] and [`Cache::set_stream`]. A streamed value is the same
+/// as a `Vec<u8>` value, so it can also be read by `get::<Vec<u8>>`.
+///
+/// By default, they buffer the whole value in memory.
+/// Backends which can do better (such as [`Chunked`], `FileCache` and `ObjectStoreCache`) override them.
 #[async_trait::async_trait]
 #[allow(clippy::len_without_is_empty)]
 pub trait Cache: Clone {
-    async fn get<T: Cacheable + Send + Sync>(&self, key: &str) -> anyhow::Result<Option<T>>;
-    async fn set<T: Cacheable + Send + Sync>(&self, key: &str, value: T) -> anyhow::Result<()>;
-    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>>;
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()>;
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()>;
     async fn len(&self) -> anyhow::Result<usize>;
+
+    /// Set a value which expires after `ttl`.
+    ///
+    /// Not all backends support expiration, so it returns an error by default.
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        let _ = (key, value, ttl);
+        anyhow::bail!("ttl is not supported by this cache")
+    }
+
+    /// Get a value as a stream of bytes.
+    async fn get_stream(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<ValueReader>> {
+        let bytes: Option<Vec<u8>> = self.get(key).await?;
+        Ok(bytes.map(|bytes| Box::new(Cursor::new(bytes)) as ValueReader))
+    }
+
+    /// Set a value from a stream of bytes. The value is stored when the stream ends.
+    async fn set_stream(&self, key: impl CacheKey + Send + Sync, mut reader: impl AsyncRead + Send + Unpin) -> anyhow::Result<()> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        self.set(key, bytes).await
+    }
 }
@@ -0,0 +1,78 @@
+use std::time::Duration;
+use tokio::io::AsyncRead;
+use crate::{Cache, CacheKey, Cacheable};
+
+/// [`NoopCache`] is a cache which stores nothing.
+///
+/// Every `get` is a miss, and every `set` is discarded.
+/// It's useful in tests, or where a [`Cache`] is required but caching is not wanted.
+/// See [`crate::Switchable`] to turn caching off at runtime.
+///
+/// ## Example
+///
+/// ```rust
+/// use cache_any::*;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let cache = NoopCache;
+///
+///     cache.set("a", 1).await.unwrap();
+///     assert_eq!(cache.get::<u8>("a").await.unwrap(), None);
+///     assert_eq!(cache.len().await.unwrap(), 0);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopCache;
+
+#[async_trait::async_trait]
+impl Cache for NoopCache {
+    async fn get<T: Cacheable + Send + Sync>(&self, _key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        Ok(None)
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, _key: impl CacheKey + Send + Sync, _value: T) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn delete(&self, _key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        Ok(0)
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, _key: impl CacheKey + Send + Sync, _value: T, _ttl: Duration) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn set_stream(&self, _key: impl CacheKey + Send + Sync, mut reader: impl AsyncRead + Send + Unpin) -> anyhow::Result<()> {
+        // the reader is still consumed, without buffering it.
+        tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_noop_cache() -> anyhow::Result<()> {
+        let cache = NoopCache;
+
+        cache.set("a", 1u8).await?;
+        cache.set_with_ttl("b", 2u8, Duration::from_secs(60)).await?;
+        cache.set_stream("c", [1u8, 2, 3].as_slice()).await?;
+
+        assert_eq!(cache.get::<u8>("a").await?, None);
+        assert_eq!(cache.get::<u8>("b").await?, None);
+        assert!(cache.get_stream("c").await?.is_none());
+        assert_eq!(cache.len().await?, 0);
+
+        cache.delete("a").await?;
+
+        Ok(())
+    }
+}
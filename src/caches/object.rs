@@ -0,0 +1,381 @@
+use std::any::Any;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Formatter};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// In-process cache of objects.
+///
+/// Unlike [`crate::MemoryCache`], values are not serialized. They are stored as [`Arc`]s,
+/// and [`ObjectCache::get`] hands out clones of them. So it's suitable for hot paths
+/// where values never leave the process.
+///
+/// Entries can expire after a TTL, and the oldest entries are evicted when
+/// the number of entries exceeds the limit. See [`ObjectCacheBuilder`].
+///
+/// `V` can be unsized. For example, `ObjectCache<String, dyn Any + Send + Sync>`
+/// stores values of any type. See [`ObjectCache::get_as`].
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache: ObjectCache<u64, User> = ObjectCacheBuilder::new()
+///     .ttl(Duration::from_secs(60))
+///     .max_entries(1024)
+///     .finish();
+///
+/// cache.set(1, User { name: String::from("jack") }).await;
+/// let user: Arc<User> = cache.get(&1).await.unwrap();
+/// ```
+pub struct ObjectCache<K, V: ?Sized> {
+    inner: Arc<RwLock<Inner<K, V>>>,
+}
+
+impl<K, V: ?Sized> Clone for ObjectCache<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<K, V: ?Sized> Debug for ObjectCache<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectCache").finish_non_exhaustive()
+    }
+}
+
+impl<K, V> Default for ObjectCache<K, V>
+where
+    K: Hash + Eq + Clone + Send + Sync,
+    V: ?Sized + Send + Sync,
+{
+    fn default() -> Self {
+        ObjectCacheBuilder::new().finish()
+    }
+}
+
+impl<K, V> ObjectCache<K, V>
+where
+    K: Hash + Eq + Clone + Send + Sync,
+    V: ?Sized + Send + Sync,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a clone of the stored [`Arc`]. Expired entries are treated as missing.
+    pub async fn get<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let now = Instant::now();
+        {
+            let inner = self.inner.read().await;
+            let entry = inner.map.get(key)?;
+            if !entry.is_expired(now) {
+                return Some(entry.value.clone());
+            }
+        }
+
+        self.inner.write().await.purge_expired(now);
+        None
+    }
+
+    pub async fn set(&self, key: K, value: V)
+    where
+        V: Sized,
+    {
+        self.set_arc(key, Arc::new(value)).await
+    }
+
+    /// Set an [`Arc`] directly. It's useful when `V` is unsized.
+    pub async fn set_arc(&self, key: K, value: Arc<V>) {
+        let mut inner = self.inner.write().await;
+        let expires_at = inner.ttl.and_then(|ttl| Instant::now().checked_add(ttl));
+        inner.insert(key, value, expires_at);
+    }
+
+    /// Set a value which expires after `ttl`, regardless of the default TTL.
+    ///
+    /// A `ttl` too large to represent never expires.
+    pub async fn set_with_ttl(&self, key: K, value: Arc<V>, ttl: Duration) {
+        let mut inner = self.inner.write().await;
+        inner.insert(key, value, Instant::now().checked_add(ttl));
+    }
+
+    pub async fn delete<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut inner = self.inner.write().await;
+        inner.remove(key);
+    }
+
+    /// The number of entries which are not expired.
+    pub async fn len(&self) -> usize {
+        let inner = self.inner.read().await;
+        let expired = inner.expiries.range(..=(Instant::now(), u64::MAX)).count();
+        inner.map.len() - expired
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+}
+
+impl<K> ObjectCache<K, dyn Any + Send + Sync>
+where
+    K: Hash + Eq + Clone + Send + Sync,
+{
+    /// Get a value and downcast it to `T`.
+    /// Returns `None` if the value is missing or is not a `T`.
+    pub async fn get_as<T, Q>(&self, key: &Q) -> Option<Arc<T>>
+    where
+        T: Any + Send + Sync,
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).await?
+            .downcast::<T>()
+            .ok()
+    }
+}
+
+/// [`ObjectCacheBuilder`] is used to build an [`ObjectCache`].
+#[derive(Debug, Clone, Default)]
+pub struct ObjectCacheBuilder {
+    ttl: Option<Duration>,
+    max_entries: Option<usize>,
+}
+
+impl ObjectCacheBuilder {
+    /// Create a new [`ObjectCacheBuilder`]. By default, entries never expire and
+    /// the number of entries is unlimited.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the default TTL of entries.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Set the maximum number of entries. When it's exceeded, the oldest ones are evicted.
+    /// Expired entries are removed on writes regardless of the limit.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Finish and build an [`ObjectCache`].
+    pub fn finish<K, V: ?Sized>(self) -> ObjectCache<K, V> {
+        ObjectCache {
+            inner: Arc::new(RwLock::new(Inner {
+                map: HashMap::new(),
+                order: BTreeMap::new(),
+                expiries: BTreeMap::new(),
+                seq: 0,
+                ttl: self.ttl,
+                max_entries: self.max_entries,
+            }))
+        }
+    }
+}
+
+struct Entry<V: ?Sized> {
+    value: Arc<V>,
+    expires_at: Option<Instant>,
+    seq: u64,
+}
+
+impl<V: ?Sized> Entry<V> {
+    fn is_expired(&self, now: Instant) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= now)
+    }
+}
+
+struct Inner<K, V: ?Sized> {
+    map: HashMap<K, Entry<V>>,
+    /// Insertion order of keys, used for eviction.
+    order: BTreeMap<u64, K>,
+    /// Expiration times of keys, used for removing expired entries.
+    expiries: BTreeMap<(Instant, u64), K>,
+    seq: u64,
+    ttl: Option<Duration>,
+    max_entries: Option<usize>,
+}
+
+impl<K: Hash + Eq + Clone, V: ?Sized> Inner<K, V> {
+    fn insert(&mut self, key: K, value: Arc<V>, expires_at: Option<Instant>) {
+        self.purge_expired(Instant::now());
+
+        self.seq += 1;
+        let entry = Entry { value, expires_at, seq: self.seq };
+
+        self.order.insert(self.seq, key.clone());
+        if let Some(expires_at) = expires_at {
+            self.expiries.insert((expires_at, self.seq), key.clone());
+        }
+        if let Some(old) = self.map.insert(key, entry) {
+            self.unindex(&old);
+        }
+
+        if let Some(max_entries) = self.max_entries {
+            while self.map.len() > max_entries {
+                let Some((_, key)) = self.order.pop_first() else {
+                    break;
+                };
+                self.remove(&key);
+            }
+        }
+    }
+
+    fn remove<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(old) = self.map.remove(key) {
+            self.unindex(&old);
+        }
+    }
+
+    /// Remove entries expired at `now`, from the earliest one.
+    fn purge_expired(&mut self, now: Instant) {
+        while let Some(first) = self.expiries.first_entry() {
+            if first.key().0 > now {
+                break;
+            }
+
+            let key = first.remove();
+            self.remove(&key);
+        }
+    }
+
+    fn unindex(&mut self, entry: &Entry<V>) {
+        self.order.remove(&entry.seq);
+        if let Some(expires_at) = entry.expires_at {
+            self.expiries.remove(&(expires_at, entry.seq));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        name: String,
+    }
+
+    #[tokio::test]
+    async fn test_object_cache() -> anyhow::Result<()> {
+        let cache: ObjectCache<String, User> = ObjectCache::new();
+        assert!(cache.is_empty().await);
+
+        cache.set(String::from("a"), User { name: String::from("jack") }).await;
+        let a = cache.get("a").await.unwrap();
+        let b = cache.get("a").await.unwrap();
+        assert_eq!(a.name, "jack");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(cache.get("b").await, None);
+
+        let cloned = cache.clone();
+        cloned.set(String::from("a"), User { name: String::from("rose") }).await;
+        assert_eq!(cache.get("a").await.unwrap().name, "rose");
+        assert_eq!(cache.len().await, 1);
+
+        cache.delete("a").await;
+        assert_eq!(cloned.get("a").await, None);
+        assert_eq!(cloned.len().await, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_object_cache_ttl() -> anyhow::Result<()> {
+        let cache: ObjectCache<u64, str> = ObjectCacheBuilder::new()
+            .ttl(Duration::from_millis(50))
+            .finish();
+
+        cache.set_arc(1, Arc::from("a")).await;
+        cache.set_with_ttl(2, Arc::from("b"), Duration::from_secs(60)).await;
+        assert_eq!(cache.get(&1).await.as_deref(), Some("a"));
+        assert_eq!(cache.len().await, 2);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(cache.get(&1).await, None);
+        assert_eq!(cache.get(&2).await.as_deref(), Some("b"));
+        assert_eq!(cache.len().await, 1);
+
+        // expired entries are removed without a limit on the number of entries.
+        cache.set_arc(3, Arc::from("c")).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cache.set_arc(4, Arc::from("d")).await;
+        let inner = cache.inner.read().await;
+        assert_eq!(inner.map.len(), 2);
+        assert_eq!(inner.order.len(), 2);
+        assert_eq!(inner.expiries.len(), 2);
+        drop(inner);
+
+        cache.set_with_ttl(5, Arc::from("e"), Duration::MAX).await;
+        assert_eq!(cache.get(&5).await.as_deref(), Some("e"));
+        let cache: ObjectCache<u64, str> = ObjectCacheBuilder::new()
+            .ttl(Duration::MAX)
+            .finish();
+        cache.set_arc(1, Arc::from("a")).await;
+        assert_eq!(cache.get(&1).await.as_deref(), Some("a"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_object_cache_eviction() -> anyhow::Result<()> {
+        let cache: ObjectCache<u64, u64> = ObjectCacheBuilder::new()
+            .max_entries(3)
+            .finish();
+
+        for i in 0..5 {
+            cache.set(i, i).await;
+        }
+        assert_eq!(cache.len().await, 3);
+        assert_eq!(cache.get(&0).await, None);
+        assert_eq!(cache.get(&1).await, None);
+        assert_eq!(cache.get(&4).await.as_deref(), Some(&4));
+
+        // overwriting makes an entry the newest one.
+        cache.set(2, 20).await;
+        cache.set(5, 5).await;
+        assert_eq!(cache.get(&3).await, None);
+        assert_eq!(cache.get(&2).await.as_deref(), Some(&20));
+
+        cache.delete(&2).await;
+        cache.set(6, 6).await;
+        assert_eq!(cache.len().await, 3);
+        assert_eq!(cache.get(&4).await.as_deref(), Some(&4));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_object_cache_any() -> anyhow::Result<()> {
+        let cache: ObjectCache<&'static str, dyn Any + Send + Sync> = ObjectCache::new();
+
+        cache.set_arc("user", Arc::new(User { name: String::from("jack") })).await;
+        cache.set_arc("n", Arc::new(1u8)).await;
+
+        assert_eq!(cache.get_as::<User, _>("user").await.unwrap().name, "jack");
+        assert_eq!(cache.get_as::<u8, _>("n").await.as_deref(), Some(&1));
+        assert!(cache.get_as::<u16, _>("n").await.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,434 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures_util::{StreamExt, TryStreamExt};
+use object_store::buffered::{BufReader, BufWriter};
+use object_store::path::Path;
+use object_store::{Attribute, Attributes, GetOptions, GetResult, ObjectMeta, ObjectStore, PutOptions};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use crate::{Cache, CacheKey, Cacheable, ValueReader};
+
+/// The attribute storing the expiration time, as milliseconds since the unix epoch.
+const EXPIRES_AT: &str = "cache-any-expires-at";
+
+/// The longest object name, which is the limit of file names on most filesystems.
+const MAX_NAME_LEN: usize = 255;
+
+/// The number of values fetched at the same time by [`ObjectStoreCache::scan`].
+const SCAN_CONCURRENCY: usize = 16;
+
+/// [`ObjectStoreCache`] is a cache using object storage to store data.
+///
+/// It works with any [`ObjectStore`], such as the local filesystem, memory, or S3-compatible storage.
+/// Feature `object_store` must be enabled.
+///
+/// Each value is an object named by the hex-encoded key under a prefix (`cache` by default),
+/// so values can be read by other tools. Keys longer than 127 bytes would exceed the
+/// 255-byte limit of file names, so they are named `sha256:` followed by the hex-encoded
+/// SHA-256 of the key instead. It's suited to large values, which are streamed
+/// by [`Cache::get_stream`] and [`Cache::set_stream`] without being buffered in memory.
+///
+/// ## Metadata
+///
+/// The content type of objects is set by [`ObjectStoreCacheBuilder::content_type`],
+/// and the expiration time set by [`Cache::set_with_ttl`] is stored in the `cache-any-expires-at` metadata.
+/// Expired objects are never returned, but they are kept until [`ObjectStoreCache::purge_expired`] is called.
+///
+/// Some stores (such as [`object_store::local::LocalFileSystem`]) don't support metadata.
+/// Use [`ObjectStoreCacheBuilder::attributes`] to disable it, which disables TTL too.
+///
+/// **Note**: [`Cache::len`] lists all objects under the prefix,
+/// which includes expired objects not yet purged.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let store = AmazonS3Builder::from_env().with_bucket_name("artifacts").build()?;
+/// let cache = ObjectStoreCacheBuilder::new(Arc::new(store))
+///     .prefix("models")
+///     .content_type("application/octet-stream")
+///     .finish();
+///
+/// cache.set_stream("resnet50", File::open("resnet50.onnx").await?).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ObjectStoreCache {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    content_type: Option<String>,
+    attributes: bool,
+}
+
+impl ObjectStoreCache {
+    /// Get all values whose keys start with `prefix`, in the order of keys.
+    ///
+    /// Keys longer than 127 bytes are stored by their hashes, so they are not returned.
+    pub async fn scan<T: Cacheable + Send + Sync>(&self, prefix: impl CacheKey) -> anyhow::Result<Vec<(Vec<u8>, T)>> {
+        let prefix = hex::encode(prefix.to_key());
+        let objects = match prefix.len() {
+            0 => self.list().await?,
+            // every matching name is greater than the prefix without its last digit.
+            // stores don't list objects in order (for example, the local filesystem),
+            // so the listing can't stop at the first name which doesn't match.
+            len => {
+                let offset = self.inner.prefix.child(&prefix[..len - 1]);
+                self.inner.store.list_with_offset(Some(&self.inner.prefix), &offset)
+                    .try_collect()
+                    .await?
+            },
+        };
+
+        let mut keys: Vec<Vec<u8>> = objects.iter()
+            .filter_map(|meta| meta.location.filename())
+            .filter(|name| name.starts_with(&prefix))
+            .filter_map(|name| hex::decode(name).ok())
+            .collect();
+        keys.sort_unstable();
+
+        let values: Vec<Option<(Vec<u8>, T)>> = futures_util::stream::iter(keys)
+            .map(|key| async move {
+                // the object may be deleted or expired after listing.
+                let value = self.get(key.as_slice()).await?;
+                anyhow::Ok(value.map(|value| (key, value)))
+            })
+            .buffered(SCAN_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        Ok(values.into_iter().flatten().collect())
+    }
+
+    /// Delete expired objects, and return the number of deleted objects.
+    /// It does nothing if metadata is disabled.
+    pub async fn purge_expired(&self) -> anyhow::Result<u64> {
+        if !self.inner.attributes {
+            return Ok(0);
+        }
+
+        let mut purged = 0;
+        for meta in self.list().await? {
+            if let Some(result) = self.head(&meta.location).await? {
+                if expired(&result.attributes) {
+                    self.remove(&meta.location).await?;
+                    purged += 1;
+                }
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// The underlying store.
+    pub fn store(&self) -> &Arc<dyn ObjectStore> {
+        &self.inner.store
+    }
+
+    fn path(&self, key: &[u8]) -> Path {
+        self.inner.prefix.child(object_name(key))
+    }
+
+    fn attributes(&self, ttl: Option<Duration>) -> anyhow::Result<Attributes> {
+        let mut attributes = Attributes::new();
+        if !self.inner.attributes {
+            if ttl.is_some() {
+                anyhow::bail!("ttl requires metadata, see `ObjectStoreCacheBuilder::attributes`");
+            }
+            return Ok(attributes);
+        }
+
+        if let Some(content_type) = &self.inner.content_type {
+            attributes.insert(Attribute::ContentType, content_type.clone().into());
+        }
+        // a ttl too large to represent never expires.
+        if let Some(expires_at) = ttl.and_then(expires_at) {
+            attributes.insert(Attribute::Metadata(EXPIRES_AT.into()), expires_at.to_string().into());
+        }
+
+        Ok(attributes)
+    }
+
+    async fn put(&self, key: &[u8], value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        let options = PutOptions {
+            attributes: self.attributes(ttl)?,
+            ..Default::default()
+        };
+
+        self.inner.store.put_opts(&self.path(key), value.into(), options).await?;
+        Ok(())
+    }
+
+    /// Get an object without its content, or `None` if it doesn't exist.
+    async fn head(&self, path: &Path) -> anyhow::Result<Option<GetResult>> {
+        let options = GetOptions {
+            head: true,
+            ..Default::default()
+        };
+
+        match self.inner.store.get_opts(path, options).await {
+            Ok(result) => Ok(Some(result)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn remove(&self, path: &Path) -> anyhow::Result<()> {
+        match self.inner.store.delete(path).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self) -> anyhow::Result<Vec<ObjectMeta>> {
+        let objects = self.inner.store.list(Some(&self.inner.prefix))
+            .try_collect()
+            .await?;
+
+        Ok(objects)
+    }
+}
+
+#[async_trait::async_trait]
+impl Cache for ObjectStoreCache {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let result = match self.inner.store.get(&self.path(&key.to_key())).await {
+            Ok(result) => result,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        if expired(&result.attributes) {
+            return Ok(None);
+        }
+
+        let bytes = result.bytes().await?;
+        Ok(Some(T::from_bytes(&bytes)?))
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        self.put(&key.to_key(), value.to_bytes(), None).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        self.remove(&self.path(&key.to_key())).await
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        Ok(self.list().await?.len())
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        self.put(&key.to_key(), value.to_bytes(), Some(ttl)).await
+    }
+
+    async fn get_stream(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<ValueReader>> {
+        let Some(result) = self.head(&self.path(&key.to_key())).await? else {
+            return Ok(None);
+        };
+
+        if expired(&result.attributes) {
+            return Ok(None);
+        }
+
+        Ok(Some(Box::new(BufReader::new(self.inner.store.clone(), &result.meta))))
+    }
+
+    async fn set_stream(&self, key: impl CacheKey + Send + Sync, mut reader: impl AsyncRead + Send + Unpin) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(self.inner.store.clone(), self.path(&key.to_key()))
+            .with_attributes(self.attributes(None)?);
+
+        let result = async {
+            tokio::io::copy(&mut reader, &mut writer).await?;
+            writer.shutdown().await
+        }.await;
+
+        // abort the multipart upload, so that no partial object is left.
+        if let Err(e) = result {
+            let _ = writer.abort().await;
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// The hex-encoded key, or its hash if the key is too long.
+fn object_name(key: &[u8]) -> String {
+    if key.len() * 2 <= MAX_NAME_LEN {
+        hex::encode(key)
+    } else {
+        format!("sha256:{}", hex::encode(Sha256::digest(key)))
+    }
+}
+
+/// The expiration time after `ttl`, or `None` if it overflows.
+fn expires_at(ttl: Duration) -> Option<i64> {
+    i64::try_from(ttl.as_millis()).ok()
+        .and_then(|ttl| unix_millis(SystemTime::now()).checked_add(ttl))
+}
+
+fn expired(attributes: &Attributes) -> bool {
+    let expires_at = attributes.get(&Attribute::Metadata(EXPIRES_AT.into()))
+        .and_then(|value| value.parse::<i64>().ok());
+
+    matches!(expires_at, Some(expires_at) if expires_at <= unix_millis(SystemTime::now()))
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// [`ObjectStoreCacheBuilder`] is used to build an [`ObjectStoreCache`].
+#[derive(Debug, Clone)]
+pub struct ObjectStoreCacheBuilder {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+    content_type: Option<String>,
+    attributes: bool,
+}
+
+impl ObjectStoreCacheBuilder {
+    /// Create a new [`ObjectStoreCacheBuilder`]. You need to specify the [`ObjectStore`].
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
+        Self {
+            store,
+            prefix: String::from("cache"),
+            content_type: None,
+            attributes: true,
+        }
+    }
+
+    /// Set the prefix of objects. It's `cache` by default.
+    pub fn prefix<S: ToString>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Set the content type of objects. It's not set by default.
+    pub fn content_type<S: ToString>(mut self, content_type: S) -> Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+
+    /// Store the content type and the expiration time as metadata of objects.
+    /// It's `true` by default. Disable it for stores which don't support metadata.
+    pub fn attributes(mut self, attributes: bool) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Finish and build an [`ObjectStoreCache`].
+    pub fn finish(self) -> ObjectStoreCache {
+        ObjectStoreCache {
+            inner: Arc::new(Inner {
+                store: self.store,
+                prefix: Path::from(self.prefix),
+                content_type: self.content_type,
+                attributes: self.attributes,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::local::LocalFileSystem;
+    use object_store::memory::InMemory;
+    use tokio::io::AsyncReadExt;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_object_store_cache() -> anyhow::Result<()> {
+        let cache = ObjectStoreCacheBuilder::new(Arc::new(InMemory::new()))
+            .prefix("models")
+            .content_type("application/octet-stream")
+            .finish();
+
+        cache.set("user_id", 114514).await?;
+        cache.set("username", String::from("jack")).await?;
+        cache.set(b"user\xff".as_slice(), vec![0u8, 1, 2, 255]).await?;
+        cache.set("other", 1u8).await?;
+
+        assert_eq!(cache.get::<usize>("user_id").await?, Some(114514));
+        assert_eq!(cache.get::<String>("username").await?, Some(String::from("jack")));
+        assert_eq!(cache.get::<u8>("none").await?, None);
+        assert_eq!(cache.len().await?, 4);
+
+        let keys: Vec<Vec<u8>> = cache.scan::<Vec<u8>>("user").await?.into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec![b"user_id".to_vec(), b"username".to_vec(), b"user\xff".to_vec()]);
+        assert_eq!(cache.scan::<Vec<u8>>("").await?.len(), 4);
+        assert_eq!(cache.scan::<Vec<u8>>("username").await?.len(), 1);
+        assert!(cache.scan::<Vec<u8>>("v").await?.is_empty());
+
+        let result = cache.store().get(&Path::from(format!("models/{}", hex::encode("username")))).await?;
+        assert_eq!(result.attributes.get(&Attribute::ContentType).map(|value| value.as_ref()), Some("application/octet-stream"));
+
+        cache.delete("user_id").await?;
+        cache.delete("none").await?;
+        assert_eq!(cache.get::<usize>("user_id").await?, None);
+        assert_eq!(cache.len().await?, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_object_store_cache_ttl() -> anyhow::Result<()> {
+        let cache = ObjectStoreCacheBuilder::new(Arc::new(InMemory::new())).finish();
+
+        cache.set("a", 1u8).await?;
+        cache.set_with_ttl("b", 2u8, Duration::MAX).await?;
+        cache.set_with_ttl("c", 3u8, Duration::from_millis(10)).await?;
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+        assert_eq!(cache.get::<u8>("b").await?, Some(2));
+        assert_eq!(cache.get::<u8>("c").await?, None);
+        assert!(cache.get_stream("c").await?.is_none());
+        assert_eq!(cache.len().await?, 3);
+
+        assert_eq!(cache.purge_expired().await?, 1);
+        assert_eq!(cache.len().await?, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_object_store_cache_file() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("cache-any-object-store-{}", std::process::id()));
+        std::fs::create_dir_all(&root)?;
+
+        let cache = ObjectStoreCacheBuilder::new(Arc::new(LocalFileSystem::new_with_prefix(&root)?))
+            .attributes(false)
+            .finish();
+
+        cache.set("a", String::from("durable")).await?;
+        assert_eq!(cache.get::<String>("a").await?, Some(String::from("durable")));
+        assert!(cache.set_with_ttl("b", 1u8, Duration::from_secs(60)).await.is_err());
+
+        let value: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        cache.set_stream("large", value.as_slice()).await?;
+
+        let mut read = Vec::new();
+        cache.get_stream("large").await?.unwrap().read_to_end(&mut read).await?;
+        assert_eq!(read, value);
+        assert!(cache.get_stream("none").await?.is_none());
+        assert_eq!(cache.len().await?, 2);
+
+        // too long for a hex-encoded file name.
+        let long = "k".repeat(200);
+        cache.set(long.as_str(), 1u8).await?;
+        assert_eq!(cache.get::<u8>(long.as_str()).await?, Some(1));
+        assert!(cache.path(long.as_bytes()).filename().unwrap().starts_with("sha256:"));
+        assert_eq!(cache.path(&[b'k'; 127]).filename().unwrap(), hex::encode([b'k'; 127]));
+
+        std::fs::remove_dir_all(&root)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use redb::{Database, ReadableTable, TableDefinition};
+use crate::{Cache, CacheKey, Cacheable};
+
+/// Values are stored with their expiration time, as milliseconds since the unix epoch (0 if it never expires).
+type Table<'a> = TableDefinition<'a, &'static [u8], (i64, &'static [u8])>;
+
+/// [`RedbCache`] is a cache using [redb](https://www.redb.org), an embedded key-value store.
+///
+/// Feature `redb` must be enabled.
+///
+/// It stores data in a single file, with ACID transactions, and needs no external service.
+/// The file can be opened by one process at a time.
+///
+/// Besides [`Cache`], it supports:
+///
+/// * expiration, by [`Cache::set_with_ttl`] and [`RedbCache::purge_expired`].
+/// * scanning by prefix, by [`RedbCache::scan`].
+/// * batch operations in one transaction, by [`RedbCache::get_many`], [`RedbCache::set_many`]
+///   and [`RedbCache::delete_many`].
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache = RedbCacheBuilder::new("cache.redb")
+///     .table("cache")
+///     .finish()
+///     .await?;
+///
+/// cache.set_many(vec![("user:1", 1), ("user:2", 2)]).await?;
+/// let users: Vec<(Vec<u8>, u64)> = cache.scan("user:").await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct RedbCache {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    db: Database,
+    table: String,
+}
+
+impl Inner {
+    fn table(&self) -> Table<'_> {
+        TableDefinition::new(&self.table)
+    }
+}
+
+impl RedbCache {
+    /// Get all values whose keys start with `prefix`, in the order of keys.
+    pub async fn scan<T: Cacheable + Send + 'static>(&self, prefix: impl CacheKey) -> anyhow::Result<Vec<(Vec<u8>, T)>> {
+        let prefix = prefix.to_key();
+        let inner = self.inner.clone();
+
+        blocking(move || {
+            let now = unix_millis(SystemTime::now());
+            let txn = inner.db.begin_read()?;
+            let table = txn.open_table(inner.table())?;
+
+            let mut ret = Vec::new();
+            for item in table.range(prefix.as_slice()..)? {
+                let (key, value) = item?;
+                if !key.value().starts_with(&prefix) {
+                    break;
+                }
+
+                let (expires_at, bytes) = value.value();
+                if !is_expired(expires_at, now) {
+                    ret.push((key.value().to_vec(), T::from_bytes(bytes)?));
+                }
+            }
+
+            Ok(ret)
+        }).await
+    }
+
+    /// Get several values in one transaction.
+    /// The result has the same order as `keys`.
+    pub async fn get_many<K, T>(&self, keys: impl IntoIterator<Item = K>) -> anyhow::Result<Vec<Option<T>>>
+    where
+        K: CacheKey,
+        T: Cacheable + Send + 'static,
+    {
+        let keys: Vec<Vec<u8>> = keys.into_iter().map(|key| key.to_key()).collect();
+        let inner = self.inner.clone();
+
+        blocking(move || {
+            let now = unix_millis(SystemTime::now());
+            let txn = inner.db.begin_read()?;
+            let table = txn.open_table(inner.table())?;
+
+            keys.iter()
+                .map(|key| {
+                    let Some(value) = table.get(key.as_slice())? else {
+                        return Ok(None);
+                    };
+
+                    let (expires_at, bytes) = value.value();
+                    if is_expired(expires_at, now) {
+                        return Ok(None);
+                    }
+                    Ok(Some(T::from_bytes(bytes)?))
+                })
+                .collect()
+        }).await
+    }
+
+    /// Set several values in one transaction. Either all or none of them are set.
+    pub async fn set_many<K, T>(&self, entries: impl IntoIterator<Item = (K, T)>) -> anyhow::Result<()>
+    where
+        K: CacheKey,
+        T: Cacheable,
+    {
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = entries.into_iter()
+            .map(|(key, value)| (key.to_key(), value.to_bytes()))
+            .collect();
+        let inner = self.inner.clone();
+
+        blocking(move || {
+            let txn = inner.db.begin_write()?;
+            {
+                let mut table = txn.open_table(inner.table())?;
+                for (key, value) in &entries {
+                    table.insert(key.as_slice(), (0, value.as_slice()))?;
+                }
+            }
+            txn.commit()?;
+
+            Ok(())
+        }).await
+    }
+
+    /// Delete several values in one transaction.
+    pub async fn delete_many<K: CacheKey>(&self, keys: impl IntoIterator<Item = K>) -> anyhow::Result<()> {
+        let keys: Vec<Vec<u8>> = keys.into_iter().map(|key| key.to_key()).collect();
+        let inner = self.inner.clone();
+
+        blocking(move || {
+            let txn = inner.db.begin_write()?;
+            {
+                let mut table = txn.open_table(inner.table())?;
+                for key in &keys {
+                    table.remove(key.as_slice())?;
+                }
+            }
+            txn.commit()?;
+
+            Ok(())
+        }).await
+    }
+
+    /// Delete expired values, and return the number of deleted values.
+    pub async fn purge_expired(&self) -> anyhow::Result<u64> {
+        let inner = self.inner.clone();
+
+        blocking(move || {
+            let now = unix_millis(SystemTime::now());
+            let txn = inner.db.begin_write()?;
+            let mut purged = 0;
+            {
+                let mut table = txn.open_table(inner.table())?;
+                table.retain(|_, (expires_at, _)| {
+                    let expired = is_expired(expires_at, now);
+                    purged += expired as u64;
+                    !expired
+                })?;
+            }
+            txn.commit()?;
+
+            Ok(purged)
+        }).await
+    }
+
+    async fn insert(&self, key: Vec<u8>, value: Vec<u8>, expires_at: i64) -> anyhow::Result<()> {
+        let inner = self.inner.clone();
+
+        blocking(move || {
+            let txn = inner.db.begin_write()?;
+            {
+                let mut table = txn.open_table(inner.table())?;
+                table.insert(key.as_slice(), (expires_at, value.as_slice()))?;
+            }
+            txn.commit()?;
+
+            Ok(())
+        }).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Cache for RedbCache {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let key = key.to_key();
+        let inner = self.inner.clone();
+
+        let value = blocking(move || {
+            let txn = inner.db.begin_read()?;
+            let table = txn.open_table(inner.table())?;
+
+            let Some(value) = table.get(key.as_slice())? else {
+                return Ok(None);
+            };
+            let (expires_at, bytes) = value.value();
+            if is_expired(expires_at, unix_millis(SystemTime::now())) {
+                return Ok(None);
+            }
+
+            Ok(Some(bytes.to_vec()))
+        }).await?;
+
+        value.map(|value| T::from_bytes(&value))
+            .transpose()
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        self.insert(key.to_key(), value.to_bytes(), 0).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        self.delete_many([key.to_key()]).await
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        let inner = self.inner.clone();
+
+        blocking(move || {
+            let now = unix_millis(SystemTime::now());
+            let txn = inner.db.begin_read()?;
+            let table = txn.open_table(inner.table())?;
+
+            let mut len = 0;
+            for item in table.iter()? {
+                let (_, value) = item?;
+                len += !is_expired(value.value().0, now) as usize;
+            }
+
+            Ok(len)
+        }).await
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        self.insert(key.to_key(), value.to_bytes(), expires_at(ttl)).await
+    }
+}
+
+/// The expiration time after `ttl`, or 0 (never expires) if it overflows.
+fn expires_at(ttl: Duration) -> i64 {
+    i64::try_from(ttl.as_millis()).ok()
+        .and_then(|ttl| unix_millis(SystemTime::now()).checked_add(ttl))
+        .map_or(0, |expires_at| expires_at.max(1))
+}
+
+fn is_expired(expires_at: i64, now: i64) -> bool {
+    expires_at != 0 && expires_at <= now
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+async fn blocking<T, F>(f: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await?
+}
+
+/// [`RedbCacheBuilder`] is used to build a [`RedbCache`].
+#[derive(Debug, Clone)]
+pub struct RedbCacheBuilder {
+    path: PathBuf,
+    table: String,
+}
+
+impl RedbCacheBuilder {
+    /// Create a new [`RedbCacheBuilder`]. You need to specify the path of the database file.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            table: String::from("cache"),
+        }
+    }
+
+    /// Set the table name. It's `cache` by default.
+    pub fn table<S: ToString>(mut self, table: S) -> Self {
+        self.table = table.to_string();
+        self
+    }
+
+    /// Finish and build a [`RedbCache`].
+    /// The database file and the table are created if they don't exist.
+    pub async fn finish(self) -> anyhow::Result<RedbCache> {
+        let inner = blocking(move || {
+            let inner = Inner {
+                db: Database::create(&self.path)?,
+                table: self.table,
+            };
+
+            let txn = inner.db.begin_write()?;
+            txn.open_table(inner.table())?;
+            txn.commit()?;
+
+            Ok(inner)
+        }).await?;
+
+        Ok(RedbCache { inner: Arc::new(inner) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("cache-any-{}-{}.redb", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn test_redb_cache() -> anyhow::Result<()> {
+        let path = temp_path("redb");
+        let cache = RedbCacheBuilder::new(&path).finish().await?;
+
+        cache.set("a", 1).await?;
+        cache.set("b", String::from("bbb")).await?;
+        cache.set(b"\xff\x00".as_slice(), vec![0u8, 1, 2, 255]).await?;
+
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+        assert_eq!(cache.get::<String>("b").await?, Some(String::from("bbb")));
+        assert_eq!(cache.get::<Vec<u8>>(b"\xff\x00".as_slice()).await?, Some(vec![0u8, 1, 2, 255]));
+        assert_eq!(cache.get::<u8>("none").await?, None);
+        assert_eq!(cache.len().await?, 3);
+
+        cache.delete("a").await?;
+        assert_eq!(cache.get::<u8>("a").await?, None);
+        assert_eq!(cache.len().await?, 2);
+
+        // survives restarts.
+        drop(cache);
+        let cache = RedbCacheBuilder::new(&path).finish().await?;
+        assert_eq!(cache.get::<String>("b").await?, Some(String::from("bbb")));
+
+        drop(cache);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redb_cache_ttl() -> anyhow::Result<()> {
+        let path = temp_path("redb-ttl");
+        let cache = RedbCacheBuilder::new(&path).finish().await?;
+
+        cache.set("a", 1).await?;
+        cache.set_with_ttl("b", 2, Duration::MAX).await?;
+        cache.set_with_ttl("c", 3, Duration::from_millis(10)).await?;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(expires_at(Duration::MAX), 0);
+
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+        assert_eq!(cache.get::<u8>("b").await?, Some(2));
+        assert_eq!(cache.get::<u8>("c").await?, None);
+        assert_eq!(cache.len().await?, 2);
+        assert_eq!(cache.purge_expired().await?, 1);
+        assert_eq!(cache.purge_expired().await?, 0);
+
+        drop(cache);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redb_cache_scan_and_batch() -> anyhow::Result<()> {
+        let path = temp_path("redb-batch");
+        let cache = RedbCacheBuilder::new(&path)
+            .table("users")
+            .finish()
+            .await?;
+
+        cache.set_many(vec![("user:2", 2u64), ("user:1", 1), ("user:3", 3), ("other", 0)]).await?;
+        cache.set_with_ttl("user:4", 4u64, Duration::from_millis(1)).await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let users: Vec<(Vec<u8>, u64)> = cache.scan("user:").await?;
+        assert_eq!(users, vec![
+            (b"user:1".to_vec(), 1),
+            (b"user:2".to_vec(), 2),
+            (b"user:3".to_vec(), 3),
+        ]);
+        assert!(cache.scan::<u64>("none:").await?.is_empty());
+
+        let values: Vec<Option<u64>> = cache.get_many(["user:3", "none", "user:4", "other"]).await?;
+        assert_eq!(values, vec![Some(3), None, None, Some(0)]);
+
+        cache.delete_many(["user:1", "user:2", "none"]).await?;
+        assert_eq!(cache.scan::<u64>("user:").await?, vec![(b"user:3".to_vec(), 3)]);
+        assert_eq!(cache.len().await?, 2);
+
+        drop(cache);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
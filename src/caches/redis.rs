@@ -1,8 +1,9 @@
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
+use std::time::Duration;
 use redis::AsyncCommands;
 use tokio::sync::RwLock;
-use crate::{Cache, Cacheable};
+use crate::{Cache, CacheKey, Cacheable, ValueFormat};
 
 /// Cache using redis.
 /// 
@@ -10,6 +11,9 @@ use crate::{Cache, Cacheable};
 /// Feature `redis` must be enabled.
 /// 
 /// A custom map should be specified. It will be used as the map of the redis key.
+/// Keys are stored as hash fields, which are binary-safe.
+/// 
+/// [`Cache::set_with_ttl`] expires the hash field with `HPEXPIRE`, which requires redis 7.4 or later.
 /// 
 /// [`RedisCache`] implements [`Cache`]. See [`Cache`] for more details.
 /// 
@@ -28,6 +32,9 @@ use crate::{Cache, Cacheable};
 /// // Redis Data ('aaa' is a redis hash map):
 /// // aaa: a -> Encoded(1)
 /// ```
+/// 
+/// Values are stored as hex strings by default.
+/// Use [`RedisCache::with_format`] to store raw bytes instead. See [`ValueFormat`].
 #[derive(Debug, Clone)]
 pub struct RedisCache {
     inner: Arc<RwLock<Inner>>,
@@ -35,11 +42,17 @@ pub struct RedisCache {
 
 impl RedisCache {
     pub async fn new<S: ToString>(client: redis::Client, map: S) -> anyhow::Result<Self> {
+        Self::with_format(client, map, ValueFormat::default()).await
+    }
+
+    /// Create a [`RedisCache`] which stores values in the given [`ValueFormat`].
+    pub async fn with_format<S: ToString>(client: redis::Client, map: S, format: ValueFormat) -> anyhow::Result<Self> {
         let conn = client.get_multiplexed_async_connection().await?;
 
         Ok(Self {
             inner: Arc::new(RwLock::new(Inner {
                 map: Arc::new(map.to_string()),
+                format,
                 client,
                 conn,
             }))
@@ -49,30 +62,30 @@ impl RedisCache {
 
 #[async_trait::async_trait]
 impl Cache for RedisCache {
-    async fn get<T: Cacheable + Send + Sync>(&self, key: &str) -> anyhow::Result<Option<T>> {
-        let val: Option<String> = {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let val: Option<Vec<u8>> = {
             let mut inner = self.inner.write().await;
             let map = inner.map.clone();
-            inner.conn.hget(map, key).await?
+            inner.conn.hget(map, key.to_key()).await?
         };
 
-        val.map(|val| T::from_hex(&val))
+        val.map(|val| ValueFormat::decode(&val))
             .transpose()
     }
 
-    async fn set<T: Cacheable + Send + Sync>(&self, key: &str, value: T) -> anyhow::Result<()> {
-        let val = value.to_hex();
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
         let mut inner = self.inner.write().await;
+        let val = inner.format.encode(&value);
         let map = inner.map.clone();
-        inner.conn.hset(map, key, val).await?;
+        inner.conn.hset(map, key.to_key(), val).await?;
 
         Ok(())
     }
 
-    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
         let mut inner = self.inner.write().await;
         let map = inner.map.clone();
-        inner.conn.hdel(map, key).await?;
+        inner.conn.hdel(map, key.to_key()).await?;
 
         Ok(())
     }
@@ -84,10 +97,28 @@ impl Cache for RedisCache {
 
         Ok(len as usize)
     }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        let mut inner = self.inner.write().await;
+        let val = inner.format.encode(&value);
+        let map = inner.map.clone();
+        let key = key.to_key();
+        let millis = ttl.as_millis().clamp(1, i64::MAX as u128) as i64;
+
+        redis::pipe()
+            .atomic()
+            .hset(map.as_str(), &key, val).ignore()
+            .hpexpire(map.as_str(), millis, redis::ExpireOption::NONE, &key).ignore()
+            .query_async::<()>(&mut inner.conn)
+            .await?;
+
+        Ok(())
+    }
 }
 
 struct Inner {
     map: Arc<String>,
+    format: ValueFormat,
     client: redis::Client,
     conn: redis::aio::MultiplexedConnection,
 }
@@ -97,6 +128,7 @@ impl Debug for Inner {
         f.debug_struct("RedisCache.Inner")
             .field("client", &self.client)
             .field("map", &self.map)
+            .field("format", &self.format)
             .finish()
     }
 }
@@ -139,4 +171,54 @@ mod tests {
         println!("size = {}", cloned.len().await?);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_redis_cache_ttl() -> anyhow::Result<()> {
+        let client = Client::open("redis://127.0.0.1:6379/")?;
+        let cache = RedisCache::new(client, "ttl").await?;
+
+        cache.set_with_ttl("a", 1, Duration::from_millis(10)).await?;
+        cache.set_with_ttl("b", 2, Duration::from_secs(60)).await?;
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(cache.get::<u8>("a").await?, None);
+        assert_eq!(cache.get::<u8>("b").await?, Some(2));
+
+        cache.delete("b").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redis_cache_keys() -> anyhow::Result<()> {
+        let client = Client::open("redis://127.0.0.1:6379/")?;
+        let cache = RedisCache::new(client, "aaa").await?;
+
+        cache.set(("user", 42), String::from("jack")).await?;
+        assert_eq!(cache.get::<String>(("user", 42)).await?, Some(String::from("jack")));
+        assert_eq!(cache.get::<String>("user:42").await?, None);
+
+        cache.set(vec![0u8, 255], 2).await?;
+        assert_eq!(cache.get::<u8>([0u8, 255]).await?, Some(2));
+        cache.delete([0u8, 255]).await?;
+        assert_eq!(cache.get::<u8>([0u8, 255]).await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redis_cache_binary() -> anyhow::Result<()> {
+        let client = Client::open("redis://127.0.0.1:6379/")?;
+        let hex = RedisCache::new(client.clone(), "bbb").await?;
+        let binary = RedisCache::with_format(client, "bbb", ValueFormat::Binary).await?;
+
+        hex.set("a", String::from("hex")).await?;
+        binary.set("b", String::from("binary")).await?;
+
+        assert_eq!(binary.get::<String>("a").await?, Some(String::from("hex")));
+        assert_eq!(binary.get::<String>("b").await?, Some(String::from("binary")));
+        assert_eq!(hex.get::<String>("b").await?, Some(String::from("binary")));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,450 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::{Cache, CacheKey, Cacheable};
+
+/// [`SqliteCache`] is a cache using sqlite to store data.
+///
+/// It uses [`sqlx::SqlitePool`] to connect to sqlite.
+/// Feature `sqlite` must be enabled.
+///
+/// It's a durable, single-node cache, which needs no server.
+///
+/// ## Prepare
+///
+/// Create a table named `cache` with the following schema,
+/// or let [`SqliteCacheBuilder::create_table`] create it:
+///
+/// ```sql
+/// CREATE TABLE IF NOT EXISTS cache (
+///     name blob not null,
+///     val blob not null,
+///     primary key (name)
+/// );
+/// ```
+///
+/// **Note**:
+/// 1. You can change the table name and the field names.
+/// 2. Keys and values are stored as raw bytes, so binary keys are supported.
+///
+/// ## Expiration
+///
+/// To use [`Cache::set_with_ttl`], add a nullable integer field storing the expiration time,
+/// as milliseconds since the unix epoch, and set it by [`SqliteCacheBuilder::expires_field`]:
+///
+/// ```sql
+/// CREATE TABLE IF NOT EXISTS cache (
+///     name blob not null,
+///     val blob not null,
+///     expires_at integer,
+///     primary key (name)
+/// );
+/// ```
+///
+/// Expired values are never returned, but they are kept in the table until
+/// [`SqliteCache::purge_expired`] is called.
+///
+/// ## Build
+///
+/// Use [`SqliteCacheBuilder`] to build a [`SqliteCache`].
+/// The database is switched to WAL mode by default.
+///
+/// ```rust,ignore
+/// let pool = SqlitePool::connect("sqlite://cache.db?mode=rwc").await?;
+/// let cache = SqliteCacheBuilder::new(pool)
+///     .table("cache")
+///     .key_field("name")
+///     .value_field("val")
+///     .expires_field("expires_at")
+///     .create_table(true)
+///     .finish()
+///     .await?;
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct SqliteCache {
+    inner: Arc<Inner>,
+}
+
+impl SqliteCache {
+    /// Delete expired values, and return the number of deleted values.
+    /// It does nothing if the expiration field is not set.
+    pub async fn purge_expired(&self) -> anyhow::Result<u64> {
+        let Some(expires_field) = &self.inner.expires_field else {
+            return Ok(0);
+        };
+
+        let sql = format!(r#"
+            DELETE FROM {}
+            WHERE {} <= ?
+        "#, &self.inner.table, expires_field);
+
+        let result = sqlx::query(&sql)
+            .bind(unix_millis(SystemTime::now()))
+            .execute(&self.inner.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn upsert(&self, key: Vec<u8>, value: Vec<u8>, expires_at: Option<i64>) -> anyhow::Result<()> {
+        match &self.inner.expires_field {
+            None => {
+                let sql = format!(r#"
+                    INSERT INTO {} ({}, {})
+                    VALUES (?, ?)
+                    ON CONFLICT ({}) DO UPDATE SET {} = excluded.{}
+                "#,
+                    &self.inner.table,
+                    &self.inner.key_field,
+                    &self.inner.value_field,
+                    &self.inner.key_field,
+                    &self.inner.value_field,
+                    &self.inner.value_field,
+                );
+
+                sqlx::query(&sql)
+                    .bind(key)
+                    .bind(value)
+                    .execute(&self.inner.pool)
+                    .await?;
+            },
+            Some(expires_field) => {
+                let sql = format!(r#"
+                    INSERT INTO {} ({}, {}, {})
+                    VALUES (?, ?, ?)
+                    ON CONFLICT ({}) DO UPDATE SET {} = excluded.{}, {} = excluded.{}
+                "#,
+                    &self.inner.table,
+                    &self.inner.key_field,
+                    &self.inner.value_field,
+                    expires_field,
+                    &self.inner.key_field,
+                    &self.inner.value_field,
+                    &self.inner.value_field,
+                    expires_field,
+                    expires_field,
+                );
+
+                sqlx::query(&sql)
+                    .bind(key)
+                    .bind(value)
+                    .bind(expires_at)
+                    .execute(&self.inner.pool)
+                    .await?;
+            },
+        }
+
+        Ok(())
+    }
+
+    /// The condition which excludes expired values.
+    fn alive(&self) -> String {
+        match &self.inner.expires_field {
+            None => String::from("1 = 1"),
+            Some(expires_field) => format!("({} IS NULL OR {} > ?)", expires_field, expires_field),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Cache for SqliteCache {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let sql = format!(r#"
+            SELECT {}
+            FROM {}
+            WHERE {} = ? AND {}
+            LIMIT 1
+        "#, &self.inner.value_field, &self.inner.table, &self.inner.key_field, self.alive());
+
+        let mut query = sqlx::query_as(&sql).bind(key.to_key());
+        if self.inner.expires_field.is_some() {
+            query = query.bind(unix_millis(SystemTime::now()));
+        }
+        let value: Option<(Vec<u8>,)> = query.fetch_optional(&self.inner.pool).await?;
+
+        let result = value.map(|value| T::from_bytes(&value.0))
+            .transpose()?;
+
+        Ok(result)
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        self.upsert(key.to_key(), value.to_bytes(), None).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        let sql = format!(r#"
+            DELETE FROM {}
+            WHERE {} = ?
+        "#, &self.inner.table, &self.inner.key_field);
+
+        sqlx::query(&sql)
+            .bind(key.to_key())
+            .execute(&self.inner.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        let sql = format!(r#"
+            SELECT COUNT(*)
+            FROM {}
+            WHERE {}
+        "#, &self.inner.table, self.alive());
+
+        let mut query = sqlx::query_as(&sql);
+        if self.inner.expires_field.is_some() {
+            query = query.bind(unix_millis(SystemTime::now()));
+        }
+        let count: (i64,) = query.fetch_optional(&self.inner.pool).await?.unwrap_or_default();
+
+        Ok(count.0 as usize)
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        if self.inner.expires_field.is_none() {
+            anyhow::bail!("ttl requires an expiration field, see `SqliteCacheBuilder::expires_field`");
+        }
+
+        self.upsert(key.to_key(), value.to_bytes(), expires_at(ttl)).await
+    }
+}
+
+/// The expiration time after `ttl`, or `None` (never expires) if it overflows.
+fn expires_at(ttl: Duration) -> Option<i64> {
+    i64::try_from(ttl.as_millis()).ok()
+        .and_then(|ttl| unix_millis(SystemTime::now()).checked_add(ttl))
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// [`SqliteCacheBuilder`] is used to build a [`SqliteCache`].
+#[derive(Debug, Clone)]
+pub struct SqliteCacheBuilder {
+    key_field: String,
+    value_field: String,
+    expires_field: Option<String>,
+    create_table: bool,
+    wal: bool,
+    table: String,
+    pool: sqlx::SqlitePool,
+}
+
+impl SqliteCacheBuilder {
+    /// Create a new [`SqliteCacheBuilder`]. You need to specify the [`sqlx::SqlitePool`].
+    pub fn new(pool: sqlx::SqlitePool) -> Self {
+        Self {
+            key_field: String::from("name"),
+            value_field: String::from("val"),
+            expires_field: None,
+            create_table: false,
+            wal: true,
+            table: String::from("cache"),
+            pool,
+        }
+    }
+
+    /// Set the key field.
+    pub fn key_field<S: ToString>(mut self, key: S) -> Self {
+        self.key_field = key.to_string();
+        self
+    }
+
+    /// Set the value field.
+    pub fn value_field<S: ToString>(mut self, value: S) -> Self {
+        self.value_field = value.to_string();
+        self
+    }
+
+    /// Set the field storing the expiration time, which enables [`Cache::set_with_ttl`].
+    pub fn expires_field<S: ToString>(mut self, field: S) -> Self {
+        self.expires_field = Some(field.to_string());
+        self
+    }
+
+    /// Create the table (and an index on the expiration field) if it doesn't exist.
+    /// It's `false` by default.
+    pub fn create_table(mut self, create_table: bool) -> Self {
+        self.create_table = create_table;
+        self
+    }
+
+    /// Switch the database to WAL mode, so that readers don't block the writer.
+    /// It's `true` by default.
+    pub fn wal(mut self, wal: bool) -> Self {
+        self.wal = wal;
+        self
+    }
+
+    /// Set the table name.
+    pub fn table<S: ToString>(mut self, table: S) -> Self {
+        self.table = table.to_string();
+        self
+    }
+
+    /// Finish and build a [`SqliteCache`].
+    pub async fn finish(self) -> anyhow::Result<SqliteCache> {
+        if self.wal {
+            sqlx::query("PRAGMA journal_mode = WAL")
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if self.create_table {
+            let expires_column = self.expires_field.as_ref()
+                .map(|field| format!(", {} integer", field))
+                .unwrap_or_default();
+
+            let sql = format!(r#"
+                CREATE TABLE IF NOT EXISTS {} (
+                    {} blob not null,
+                    {} blob not null{},
+                    primary key ({})
+                )
+            "#, &self.table, &self.key_field, &self.value_field, expires_column, &self.key_field);
+            sqlx::query(&sql).execute(&self.pool).await?;
+
+            if let Some(expires_field) = &self.expires_field {
+                let sql = format!(r#"
+                    CREATE INDEX IF NOT EXISTS {}_{}
+                    ON {} ({})
+                "#, &self.table, expires_field, &self.table, expires_field);
+                sqlx::query(&sql).execute(&self.pool).await?;
+            }
+        }
+
+        Ok(SqliteCache {
+            inner: Arc::new(Inner {
+                key_field: self.key_field,
+                value_field: self.value_field,
+                expires_field: self.expires_field,
+                table: self.table,
+                pool: self.pool,
+            })
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    key_field: String,
+    value_field: String,
+    expires_field: Option<String>,
+    table: String,
+    pool: sqlx::SqlitePool,
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+    use sqlx::SqlitePool;
+    use super::*;
+
+    /// Every connection to `sqlite::memory:` opens a new database, so only one is used.
+    async fn memory_pool() -> anyhow::Result<SqlitePool> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await?;
+
+        Ok(pool)
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_cache() -> anyhow::Result<()> {
+        let cache = SqliteCacheBuilder::new(memory_pool().await?)
+            .table("my_cache")
+            .create_table(true)
+            .finish()
+            .await?;
+
+        cache.set("user_id", 114514).await?;
+        cache.set("username", String::from("jack")).await?;
+        cache.set(b"\xff\x00".as_slice(), vec![0u8, 1, 2, 255]).await?;
+
+        assert_eq!(cache.get::<usize>("user_id").await?, Some(114514));
+        assert_eq!(cache.get::<String>("username").await?, Some(String::from("jack")));
+        assert_eq!(cache.get::<Vec<u8>>(b"\xff\x00".as_slice()).await?, Some(vec![0u8, 1, 2, 255]));
+        assert_eq!(cache.len().await?, 3);
+
+        cache.set("username", String::from("rose")).await?;
+        assert_eq!(cache.get::<String>("username").await?, Some(String::from("rose")));
+
+        cache.delete("user_id").await?;
+        assert_eq!(cache.get::<usize>("user_id").await?, None);
+        assert_eq!(cache.len().await?, 2);
+
+        assert!(cache.set_with_ttl("a", 1, Duration::from_secs(60)).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_cache_ttl() -> anyhow::Result<()> {
+        let cache = SqliteCacheBuilder::new(memory_pool().await?)
+            .expires_field("expires_at")
+            .create_table(true)
+            .finish()
+            .await?;
+
+        cache.set("a", 1).await?;
+        cache.set_with_ttl("b", 2, Duration::from_secs(60)).await?;
+        cache.set_with_ttl("c", 3, Duration::from_millis(10)).await?;
+        assert_eq!(cache.len().await?, 3);
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+        assert_eq!(cache.get::<u8>("b").await?, Some(2));
+        assert_eq!(cache.get::<u8>("c").await?, None);
+        assert_eq!(cache.len().await?, 2);
+        assert_eq!(cache.purge_expired().await?, 1);
+
+        // a plain set clears the expiration.
+        cache.set_with_ttl("a", 1, Duration::from_millis(10)).await?;
+        cache.set("a", 1).await?;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+
+        // a ttl too large to represent never expires.
+        cache.set_with_ttl("d", 4, Duration::MAX).await?;
+        assert_eq!(cache.get::<u8>("d").await?, Some(4));
+        assert_eq!(expires_at(Duration::MAX), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_cache_file() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("cache-any-{}.db", std::process::id()));
+        let url = format!("sqlite://{}?mode=rwc", path.display());
+
+        let cache = SqliteCacheBuilder::new(SqlitePool::connect(&url).await?)
+            .create_table(true)
+            .finish()
+            .await?;
+        cache.set("a", String::from("durable")).await?;
+
+        let (mode,): (String,) = sqlx::query_as("PRAGMA journal_mode")
+            .fetch_one(&cache.inner.pool)
+            .await?;
+        assert_eq!(mode, "wal");
+        cache.inner.pool.close().await;
+
+        let cache = SqliteCacheBuilder::new(SqlitePool::connect(&url).await?)
+            .finish()
+            .await?;
+        assert_eq!(cache.get::<String>("a").await?, Some(String::from("durable")));
+        cache.inner.pool.close().await;
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncRead;
+use crate::{Cache, CacheKey, Cacheable, NoopCache, ValueReader};
+
+/// A cache wrapper whose caching can be turned off at runtime.
+///
+/// When it's disabled, it behaves like [`NoopCache`]: every `get` is a miss,
+/// and every `set` and `delete` is discarded, without accessing the wrapped cache.
+/// So it can be disabled when the backend is down, without changing types or code paths.
+///
+/// Values changed while it's disabled may be stale after it's enabled again.
+/// With [`Switchable::forward_deletes`], deletes are still forwarded to the wrapped cache
+/// as a best effort and their errors are ignored. But each of them waits for the wrapped cache,
+/// which may be a timeout when the backend is down.
+///
+/// The switch is shared by clones, so disabling any clone disables all of them.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache = Switchable::new(MemoryCache::default());
+///
+/// cache.set("a", 1).await?;
+/// cache.disable();
+/// assert_eq!(cache.get::<u8>("a").await?, None);
+///
+/// cache.enable();
+/// assert_eq!(cache.get::<u8>("a").await?, Some(1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Switchable<C> {
+    inner: C,
+    enabled: Arc<AtomicBool>,
+    forward_deletes: bool,
+}
+
+impl<C: Cache> Switchable<C> {
+    /// Wrap a cache. It's enabled by default.
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            enabled: Arc::new(AtomicBool::new(true)),
+            forward_deletes: false,
+        }
+    }
+
+    /// Whether to forward deletes to the wrapped cache while it's disabled. Default is false.
+    pub fn forward_deletes(mut self, forward_deletes: bool) -> Self {
+        self.forward_deletes = forward_deletes;
+        self
+    }
+
+    /// Turn caching on.
+    pub fn enable(&self) {
+        self.set_enabled(true);
+    }
+
+    /// Turn caching off.
+    pub fn disable(&self) {
+        self.set_enabled(false);
+    }
+
+    /// Turn caching on or off.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether caching is on.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Get the wrapped cache.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Unwrap and return the wrapped cache.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+#[async_trait::async_trait]
+impl<C: Cache + Send + Sync> Cache for Switchable<C> {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        if !self.is_enabled() {
+            return NoopCache.get(key).await;
+        }
+
+        self.inner.get(key).await
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        if !self.is_enabled() {
+            return NoopCache.set(key, value).await;
+        }
+
+        self.inner.set(key, value).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        if !self.is_enabled() {
+            if self.forward_deletes {
+                // best effort, as the wrapped cache may be down.
+                let _ = self.inner.delete(key).await;
+            }
+            return Ok(());
+        }
+
+        self.inner.delete(key).await
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        if !self.is_enabled() {
+            return NoopCache.len().await;
+        }
+
+        self.inner.len().await
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        if !self.is_enabled() {
+            return NoopCache.set_with_ttl(key, value, ttl).await;
+        }
+
+        self.inner.set_with_ttl(key, value, ttl).await
+    }
+
+    async fn get_stream(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<ValueReader>> {
+        if !self.is_enabled() {
+            return NoopCache.get_stream(key).await;
+        }
+
+        self.inner.get_stream(key).await
+    }
+
+    async fn set_stream(&self, key: impl CacheKey + Send + Sync, reader: impl AsyncRead + Send + Unpin) -> anyhow::Result<()> {
+        if !self.is_enabled() {
+            return NoopCache.set_stream(key, reader).await;
+        }
+
+        self.inner.set_stream(key, reader).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MemoryCache;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_switchable() -> anyhow::Result<()> {
+        let cache = Switchable::new(MemoryCache::default());
+        assert!(cache.is_enabled());
+
+        cache.set("a", 1u8).await?;
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+
+        // the switch is shared by clones.
+        let cloned = cache.clone();
+        cloned.disable();
+        assert!(!cache.is_enabled());
+
+        assert_eq!(cache.get::<u8>("a").await?, None);
+        assert_eq!(cache.len().await?, 0);
+        cache.set("b", 2u8).await?;
+        cache.set_stream("c", [1u8, 2, 3].as_slice()).await?;
+        assert_eq!(cache.inner().get::<u8>("b").await?, None);
+        assert_eq!(cache.inner().len().await?, 1);
+
+        // deletes don't reach the wrapped cache by default.
+        cache.delete("a").await?;
+        assert_eq!(cache.inner().len().await?, 1);
+
+        cache.enable();
+        assert_eq!(cache.get::<u8>("a").await?, Some(1));
+        assert_eq!(cache.get::<u8>("b").await?, None);
+        assert!(cache.get_stream("c").await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_switchable_forward_deletes() -> anyhow::Result<()> {
+        let cache = Switchable::new(MemoryCache::default()).forward_deletes(true);
+        cache.set("a", 1u8).await?;
+
+        cache.disable();
+        cache.delete("a").await?;
+        assert_eq!(cache.inner().len().await?, 0);
+
+        cache.enable();
+        assert_eq!(cache.get::<u8>("a").await?, None);
+
+        Ok(())
+    }
+}
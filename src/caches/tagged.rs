@@ -0,0 +1,242 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Cursor, Read};
+use std::time::Duration;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use crate::{Cache, CacheKey, Cacheable};
+
+const MAGIC: [u8; 4] = [0xca, 0xa7, 0x7a, 0x60];
+
+/// A cache wrapper that tags every value with its type.
+///
+/// [`TypeTagged::set`] writes an envelope containing [`Cacheable::type_name`]
+/// and [`Cacheable::schema_version`] in front of the encoded value.
+/// [`TypeTagged::get`] verifies the envelope and returns a [`TypeMismatch`]
+/// error if the requested type is not the stored one.
+///
+/// Values written without the envelope (for example, existing data in
+/// redis or mysql) are still readable, but they are not verified.
+///
+/// **Note**: type casting (such as storing a `u8` and reading a `u16`)
+/// is rejected by this wrapper.
+///
+/// Values with an older schema version are rejected too, unless the type accepts them
+/// (see [`Cacheable::accepts_schema_version`]). So [`crate::Versioned`] values are upgraded
+/// by [`crate::Migrate`] as usual.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache = TypeTagged::new(MemoryCache::default());
+///
+/// cache.set("a", 1u128).await.unwrap();
+/// assert_eq!(cache.get::<u128>("a").await.unwrap(), Some(1));
+///
+/// let err = cache.get::<String>("a").await.unwrap_err();
+/// assert!(err.downcast_ref::<TypeMismatch>().is_some());
+/// ```
+#[derive(Debug, Clone)]
+pub struct TypeTagged<C> {
+    inner: C,
+}
+
+impl<C: Cache> TypeTagged<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+
+    /// Get the wrapped cache.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Unwrap and return the wrapped cache.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+#[async_trait::async_trait]
+impl<C: Cache + Send + Sync> Cache for TypeTagged<C> {
+    async fn get<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let bytes: Option<Vec<u8>> = self.inner.get(key).await?;
+
+        bytes.map(|bytes| decode::<T>(&bytes))
+            .transpose()
+    }
+
+    async fn set<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        self.inner.set(key, encode(&value)).await
+    }
+
+    async fn delete(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<()> {
+        self.inner.delete(key).await
+    }
+
+    async fn len(&self) -> anyhow::Result<usize> {
+        self.inner.len().await
+    }
+
+    async fn set_with_ttl<T: Cacheable + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T, ttl: Duration) -> anyhow::Result<()> {
+        self.inner.set_with_ttl(key, encode(&value), ttl).await
+    }
+}
+
+/// Returned by [`TypeTagged::get`] when the stored value has a different type
+/// or schema version from the requested one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    pub expected: String,
+    pub expected_version: u32,
+    pub found: String,
+    pub found_version: u32,
+}
+
+impl Display for TypeMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "type mismatch: expected `{}` (version {}), found `{}` (version {})",
+            self.expected, self.expected_version, self.found, self.found_version,
+        )
+    }
+}
+
+impl std::error::Error for TypeMismatch {}
+
+fn encode<T: Cacheable>(value: &T) -> Vec<u8> {
+    let name = T::type_name().as_bytes();
+    let payload = value.to_bytes();
+
+    let mut wtr = Vec::with_capacity(MAGIC.len() + 2 + name.len() + 4 + payload.len());
+    wtr.extend_from_slice(&MAGIC);
+    wtr.write_u16::<BigEndian>(name.len() as u16).unwrap();
+    wtr.extend_from_slice(name);
+    wtr.write_u32::<BigEndian>(T::schema_version()).unwrap();
+    wtr.extend_from_slice(&payload);
+
+    wtr
+}
+
+fn decode<T: Cacheable>(bytes: &[u8]) -> anyhow::Result<T> {
+    let Some(rest) = bytes.strip_prefix(&MAGIC) else {
+        // untagged value, written before the wrapper was used.
+        return T::from_bytes(bytes);
+    };
+
+    let mut rdr = Cursor::new(rest);
+    let len = rdr.read_u16::<BigEndian>()? as usize;
+    let mut name = vec![0u8; len];
+    rdr.read_exact(&mut name)?;
+    let found = String::from_utf8(name)?;
+    let found_version = rdr.read_u32::<BigEndian>()?;
+
+    if found != T::type_name() || !T::accepts_schema_version(found_version) {
+        return Err(TypeMismatch {
+            expected: T::type_name().to_string(),
+            expected_version: T::schema_version(),
+            found,
+            found_version,
+        }.into());
+    }
+
+    let offset = rdr.position() as usize;
+    T::from_bytes(&rest[offset..])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::MemoryCache;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_type_tagged() -> anyhow::Result<()> {
+        let cache = TypeTagged::new(MemoryCache::default());
+
+        cache.set("a", 1u128).await?;
+        cache.set("b", String::from("bbb")).await?;
+
+        assert_eq!(cache.get::<u128>("a").await?, Some(1));
+        assert_eq!(cache.get::<String>("b").await?, Some(String::from("bbb")));
+        assert_eq!(cache.get::<Arc<String>>("b").await?, Some(Arc::new(String::from("bbb"))));
+        assert_eq!(cache.get::<String>("c").await?, None);
+
+        let err = cache.get::<String>("a").await.unwrap_err();
+        let err = err.downcast_ref::<TypeMismatch>().unwrap();
+        assert_eq!(err.expected, "alloc::string::String");
+        assert_eq!(err.found, "u128");
+
+        assert!(cache.get::<u8>("a").await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_type_tagged_untagged_value() -> anyhow::Result<()> {
+        let inner = MemoryCache::default();
+        inner.set("a", String::from("legacy")).await?;
+        inner.set("b", 7u32).await?;
+
+        let cache = TypeTagged::new(inner);
+        assert_eq!(cache.get::<String>("a").await?, Some(String::from("legacy")));
+        assert_eq!(cache.get::<u32>("b").await?, Some(7));
+        assert_eq!(cache.len().await?, 2);
+
+        cache.delete("a").await?;
+        assert_eq!(cache.get::<String>("a").await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_type_tagged_schema_version() -> anyhow::Result<()> {
+        #[derive(Debug, PartialEq)]
+        struct V1(u8);
+
+        impl Cacheable for V1 {
+            fn to_bytes(&self) -> Vec<u8> {
+                vec![self.0]
+            }
+
+            fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+                Ok(Self(bytes[0]))
+            }
+
+            fn type_name() -> &'static str {
+                "v"
+            }
+        }
+
+        #[derive(Debug, PartialEq)]
+        struct V2(u8);
+
+        impl Cacheable for V2 {
+            fn to_bytes(&self) -> Vec<u8> {
+                vec![self.0]
+            }
+
+            fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+                Ok(Self(bytes[0]))
+            }
+
+            fn type_name() -> &'static str {
+                "v"
+            }
+
+            fn schema_version() -> u32 {
+                2
+            }
+        }
+
+        let cache = TypeTagged::new(MemoryCache::default());
+        cache.set("v", V1(1)).await?;
+        assert_eq!(cache.get::<V1>("v").await?, Some(V1(1)));
+
+        let err = cache.get::<V2>("v").await.unwrap_err();
+        let err = err.downcast_ref::<TypeMismatch>().unwrap();
+        assert_eq!(err.expected_version, 2);
+        assert_eq!(err.found_version, 0);
+
+        Ok(())
+    }
+}
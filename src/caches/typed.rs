@@ -0,0 +1,148 @@
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use crate::{Cache, CacheKey, Cacheable};
+
+/// A typed handle on top of any [`Cache`].
+///
+/// It fixes the key type `K` and the value type `V`, so the wrong type can't be used
+/// at call sites. Keys are encoded by [`CacheKey`], and prefixed by [`TypedCache::prefix`],
+/// so that several handles can share one cache.
+///
+/// The prefix is preceded by its length in decimal and `:`, like a part of [`crate::composite_key`],
+/// so handles with different prefixes never collide, even if one prefix starts with another.
+/// Keys of a handle without a prefix are stored as is.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let cache = MemoryCache::default();
+/// let users: TypedCache<_, u64, User> = TypedCache::new(cache.clone()).prefix("user:");
+///
+/// // [SET 5:user:1 -> User]
+/// users.set(&1, User { name: String::from("jack") }).await?;
+/// let user: Option<User> = users.get(&1).await?;
+/// ```
+pub struct TypedCache<C, K: ?Sized, V> {
+    cache: C,
+    prefix: String,
+    _marker: PhantomData<fn(&K) -> V>,
+}
+
+impl<C: Clone, K: ?Sized, V> Clone for TypedCache<C, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            prefix: self.prefix.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Debug, K: ?Sized, V> Debug for TypedCache<C, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypedCache")
+            .field("cache", &self.cache)
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+impl<C, K, V> TypedCache<C, K, V>
+where
+    C: Cache + Send + Sync,
+    K: CacheKey + ?Sized,
+    V: Cacheable + Send + Sync,
+{
+    pub fn new(cache: C) -> Self {
+        Self {
+            cache,
+            prefix: String::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Set the prefix of keys. It's empty by default.
+    ///
+    /// Changing the prefix of a handle (including adding one) changes where its values are stored.
+    pub fn prefix<S: ToString>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Get the underlying cache.
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    pub async fn get(&self, key: &K) -> anyhow::Result<Option<V>> {
+        self.cache.get(self.key(key)).await
+    }
+
+    pub async fn set(&self, key: &K, value: V) -> anyhow::Result<()> {
+        self.cache.set(self.key(key), value).await
+    }
+
+    pub async fn delete(&self, key: &K) -> anyhow::Result<()> {
+        self.cache.delete(self.key(key)).await
+    }
+
+    fn key(&self, key: &K) -> Vec<u8> {
+        if self.prefix.is_empty() {
+            return key.to_key();
+        }
+
+        let mut ret = format!("{}:{}", self.prefix.len(), self.prefix).into_bytes();
+        ret.extend_from_slice(&key.to_key());
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MemoryCache;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_typed_cache() -> anyhow::Result<()> {
+        let cache = MemoryCache::default();
+        let users: TypedCache<_, u64, String> = TypedCache::new(cache.clone()).prefix("user:");
+        let names: TypedCache<_, str, u64> = TypedCache::new(cache.clone()).prefix("name:");
+
+        users.set(&1, String::from("jack")).await?;
+        names.set("jack", 1).await?;
+
+        assert_eq!(users.get(&1).await?, Some(String::from("jack")));
+        assert_eq!(users.get(&2).await?, None);
+        assert_eq!(names.get("jack").await?, Some(1));
+
+        assert_eq!(cache.get::<String>([b"5:user:".as_slice(), &1u64.to_key()].concat()).await?, Some(String::from("jack")));
+        assert_eq!(cache.len().await?, 2);
+
+        let cloned = users.clone();
+        cloned.delete(&1).await?;
+        assert_eq!(users.get(&1).await?, None);
+        assert_eq!(users.cache().len().await?, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_typed_cache_prefix() -> anyhow::Result<()> {
+        let cache = MemoryCache::default();
+        let a: TypedCache<_, str, u64> = TypedCache::new(cache.clone()).prefix("user");
+        let b: TypedCache<_, str, u64> = TypedCache::new(cache.clone()).prefix("user:");
+        let plain: TypedCache<_, str, u64> = TypedCache::new(cache.clone());
+
+        a.set(":1", 1).await?;
+        b.set("1", 2).await?;
+        plain.set("user:1", 3).await?;
+
+        assert_eq!(a.get(":1").await?, Some(1));
+        assert_eq!(b.get("1").await?, Some(2));
+        assert_eq!(plain.get("user:1").await?, Some(3));
+        assert_eq!(cache.get::<u64>("user:1").await?, Some(3));
+        assert_eq!(cache.len().await?, 3);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use std::borrow::Cow;
+
+/// used to convert a value to the key of a cache.
+///
+/// All [`crate::Cache`] methods accept a [`CacheKey`], so keys can be typed
+/// (for example, a user id or a tuple) instead of formatted strings.
+///
+/// The encoding is canonical, and keys of different types never collide:
+///
+/// * Strings are encoded as is, so existing `&str` keys are unchanged.
+/// * Integers are encoded as `0xff`, `i` and the decimal digits.
+///   `0xff` never appears in UTF-8, so `42` and `"42"` are different keys.
+///   Integers of different types with the same value are the same key.
+/// * Tuples (and structs, see [`impl_cache_key!`]) are encoded by [`composite_key`],
+///   which starts with `0xff`, `t` and prefixes each part with its length.
+/// * Binary keys (`[u8]`, `Vec<u8>`, `[u8; N]`) are encoded as is.
+///   They are supported by [`crate::MemoryCache`] and [`crate::RedisCache`].
+///   Binary keys starting with `0xff` may collide with integer or tuple keys.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// // instead of `format!("user:{}", 42)`
+/// cache.set(("user", 42), String::from("jack")).await?;
+/// ```
+pub trait CacheKey {
+    /// Convert [`CacheKey`] to the key of a cache.
+    fn to_key(&self) -> Vec<u8>;
+}
+
+impl<T: CacheKey + ?Sized> CacheKey for &T {
+    fn to_key(&self) -> Vec<u8> {
+        (**self).to_key()
+    }
+}
+
+impl CacheKey for str {
+    fn to_key(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl CacheKey for String {
+    fn to_key(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl CacheKey for Cow<'_, str> {
+    fn to_key(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl CacheKey for [u8] {
+    fn to_key(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl CacheKey for Vec<u8> {
+    fn to_key(&self) -> Vec<u8> {
+        self.clone()
+    }
+}
+
+impl<const N: usize> CacheKey for [u8; N] {
+    fn to_key(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+/// Starts integer and composite keys. It never appears in UTF-8, so they never collide with strings.
+const TYPED_KEY: u8 = 0xff;
+const INTEGER: u8 = b'i';
+const COMPOSITE: u8 = b't';
+
+macro_rules! impl_numeric {
+    ($($ty: ty),+ $(,)?) => {
+        $(
+            impl CacheKey for $ty {
+                fn to_key(&self) -> Vec<u8> {
+                    let mut key = vec![TYPED_KEY, INTEGER];
+                    key.extend_from_slice(self.to_string().as_bytes());
+                    key
+                }
+            }
+        )+
+    };
+}
+
+impl_numeric!(
+    u128, i128,
+    u64, i64,
+    u32, i32,
+    u16, i16,
+    u8, i8,
+    usize, isize,
+);
+
+macro_rules! impl_tuple {
+    ($($name: ident),+) => {
+        impl<$($name: CacheKey),+> CacheKey for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn to_key(&self) -> Vec<u8> {
+                let ($($name,)+) = self;
+                composite_key(&[$($name as &dyn CacheKey),+])
+            }
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
+impl_tuple!(A, B, C, D, E, F, G, H, I);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/// Encode several parts as one key.
+///
+/// The key starts with `0xff` and `t`, followed by each part as its length in decimal, `:` and its bytes.
+/// For example, `("a:b", "c")` is encoded as `\xfft3:a:b1:c` while `("a", "b:c")` is encoded as `\xfft1:a3:b:c`.
+/// Nested parts keep their own encoding, so the encoding is collision-free
+/// for any number and nesting of parts.
+pub fn composite_key(parts: &[&dyn CacheKey]) -> Vec<u8> {
+    let mut key = vec![TYPED_KEY, COMPOSITE];
+
+    for part in parts {
+        let part = part.to_key();
+        key.extend_from_slice(part.len().to_string().as_bytes());
+        key.push(b':');
+        key.extend_from_slice(&part);
+    }
+
+    key
+}
+
+/// Implement [`CacheKey`] for a struct by encoding its fields with [`composite_key`].
+///
+/// ## Example
+///
+/// ```rust
+/// use cache_any::{impl_cache_key, CacheKey};
+///
+/// struct UserKey {
+///     tenant: String,
+///     id: u64,
+/// }
+///
+/// impl_cache_key!(UserKey { tenant, id });
+///
+/// let key = UserKey { tenant: String::from("acme"), id: 42 };
+/// assert_eq!(key.to_key(), (String::from("acme"), 42u64).to_key());
+/// ```
+#[macro_export]
+macro_rules! impl_cache_key {
+    ($ty: ty { $($field: ident),+ $(,)? }) => {
+        impl $crate::CacheKey for $ty {
+            fn to_key(&self) -> Vec<u8> {
+                $crate::composite_key(&[$(&self.$field as &dyn $crate::CacheKey),+])
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!("a".to_key(), b"a");
+        assert_eq!(String::from("b").to_key(), b"b");
+        assert_eq!((&&"c").to_key(), b"c");
+        assert_eq!(Cow::Borrowed("d").to_key(), b"d");
+        assert_eq!(42u64.to_key(), b"\xffi42");
+        assert_eq!((-1i8).to_key(), b"\xffi-1");
+        assert_eq!(42u8.to_key(), 42i128.to_key());
+        assert_eq!([0u8, 255].to_key(), vec![0u8, 255]);
+        assert_eq!(vec![0u8, 255].as_slice().to_key(), vec![0u8, 255]);
+
+        assert_ne!(42u64.to_key(), "42".to_key());
+    }
+
+    #[test]
+    fn test_composite_key() {
+        assert_eq!(("user", 42).to_key(), b"\xfft4:user4:\xffi42");
+        assert_eq!(("a:b", "c").to_key(), b"\xfft3:a:b1:c");
+        assert_eq!(("a", "b:c").to_key(), b"\xfft1:a3:b:c");
+        assert_eq!((1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8, 10u8, 11u8, 12u8).to_key().len(), 2 + 9 * 5 + 3 * 6);
+
+        assert_ne!(("user", 42).to_key(), "user:42".to_key());
+        assert_ne!(("user", 42).to_key(), ("user", "42").to_key());
+        assert_ne!(("a:b", "c").to_key(), ("a", "b:c").to_key());
+        assert_ne!((("a", "b"), "c").to_key(), ("a:b", "c").to_key());
+        assert_ne!((("a", "b"), "c").to_key(), ("a", ("b", "c")).to_key());
+        assert_ne!(("a", "b", "c").to_key(), (("a", "b"), "c").to_key());
+        assert_ne!(("a",).to_key(), "a".to_key());
+        assert_ne!(("", "").to_key(), ("",).to_key());
+    }
+
+    #[test]
+    fn test_impl_cache_key() {
+        struct UserKey {
+            tenant: String,
+            id: u64,
+        }
+
+        impl_cache_key!(UserKey { tenant, id });
+
+        let key = UserKey { tenant: String::from("a:b"), id: 42 };
+        assert_eq!(key.to_key(), (String::from("a:b"), 42u64).to_key());
+        assert_ne!(key.to_key(), (String::from("a"), String::from("b:42")).to_key());
+    }
+}
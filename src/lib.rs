@@ -15,11 +15,27 @@
 //! 
 //! By default, it provides a simple memory cache as example. See [`caches::MemoryCache`].
 //! But it's not recommended to use [`caches::MemoryCache`] directly in production.
+//! For values that never leave the process, [`caches::ObjectCache`] stores them without serialization.
+//! For backends with size limits, [`caches::Chunked`] splits large values into chunks.
+//! To turn caching off at runtime, wrap a cache in [`caches::Switchable`]; [`caches::NoopCache`] never caches anything.
 //!
 //! Other caches are available in below features:
 //! 
 //! * `redis`: Use redis as storage backend. See [`caches::RedisCache`].
 //! * `mysql`: Use mysql as storage backend. See [`caches::MySqlCache`].
+//! * `sqlite`: Use sqlite as storage backend. See [`caches::SqliteCache`].
+//! * `postgres`: Use postgres as storage backend. See [`caches::PostgresCache`].
+//! * `sql`: Use any SQL database supported by `sqlx::AnyPool` as storage backend. See [`caches::SqlCache`].
+//!   It enables no driver by itself: enable `sqlite`, `postgres` or `mysql` with it.
+//! * `file`: Use the file system as storage backend. See [`caches::FileCache`].
+//! * `bitcask`: Use append-only log files as storage backend. See [`caches::BitcaskCache`].
+//! * `redb`: Use redb, an embedded key-value store, as storage backend. See [`caches::RedbCache`].
+//! * `memcached`: Use memcached as storage backend. See [`caches::MemcachedCache`].
+//! * `object_store`: Use object storage (local filesystem, memory, S3-compatible...) as storage backend. See [`caches::ObjectStoreCache`].
+//! * `bytes`: Store values as [`bytes::Bytes`] in [`caches::MemoryCache`], without copying.
+//! * `zstd`, `lz4`: Compress values with zstd or lz4. See [`caches::Compressed`].
+//! * `encryption`: Encrypt values at rest. See [`caches::Encrypted`].
+//! * `integrity`: Verify values with checksums or signatures. See [`caches::Checked`].
 //! 
 //! ## Usage
 //! 
@@ -32,7 +48,8 @@
 //! 
 //! ## Concepts
 //! 
-//! * **Key**: Specified by the cache implementation. Usually it is a string-like type (&str, String, ...).
+//! * **Key**: A [`CacheKey`] value. Usually it is a string-like type (&str, String, ...),
+//!   but integers, tuples and binary keys are also supported.
 //! * **Value**: The value of a cache is a [`Cacheable`] value.
 //! 
 //! [`Cacheable`] is a trait that describes how to convert a `value` to bytes and vice versa.
@@ -40,6 +57,7 @@
 //! A cache can store any value that implements [`Cacheable`].
 //! That is, you can store usize and string (or any other types) at the same time.
 //! But you need to know the exact type when you retrieve the value.
+//! To fix the key and value types of a cache, use [`caches::TypedCache`].
 //! 
 //! ## Basic Usage
 //! 
@@ -122,9 +140,15 @@
 mod cacheable;
 pub use cacheable::*;
 
+mod key;
+pub use key::*;
+
 mod caches;
 pub use caches::*;
 
+mod versioned;
+pub use versioned::*;
+
 #[test]
 fn it_works() {
     println!("it works")
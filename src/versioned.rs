@@ -0,0 +1,360 @@
+use std::io::Cursor;
+use std::marker::PhantomData;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use crate::{Cache, CacheKey, Cacheable};
+
+const MAGIC: [u8; 4] = [0xca, 0xa7, 0x5e, 0x70];
+
+/// A [`Cacheable`] type which can upgrade payloads written by older versions of itself.
+///
+/// The current version is [`Cacheable::schema_version`].
+/// Values should be stored as [`Versioned`], so that the version is written
+/// together with the payload. Reading a [`Versioned`] value (by a plain `get`)
+/// upgrades older payloads transparently.
+///
+/// Implement [`Migrate::upgrade`] to convert a payload from one version to the next.
+/// Old payloads are upgraded step by step until they reach the current version.
+/// Alternatively, override [`Migrate::migrate`] to decode older payloads directly.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// #[derive(Debug, serde::Serialize, serde::Deserialize)]
+/// struct User {
+///     name: String,
+///     age: u8, // added in version 1
+///     email: String, // added in version 2
+/// }
+///
+/// impl Cacheable for User {
+///     fn to_bytes(&self) -> Vec<u8> {
+///         serde_json::to_vec(self).unwrap()
+///     }
+///
+///     fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+///         Ok(serde_json::from_slice(bytes)?)
+///     }
+///
+///     fn schema_version() -> u32 {
+///         2
+///     }
+/// }
+///
+/// impl Migrate for User {
+///     fn upgrade(version: u32, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+///         let mut user: serde_json::Value = serde_json::from_slice(bytes)?;
+///         match version {
+///             0 => user["age"] = 0.into(),
+///             1 => user["email"] = "".into(),
+///             _ => anyhow::bail!("unknown version {}", version),
+///         }
+///         Ok(serde_json::to_vec(&user)?)
+///     }
+/// }
+///
+/// let user: Option<Versioned<User>> = cache.get("user").await?;
+/// // or upgrade and write back in the current version:
+/// let user: Option<User> = cache.get_migrated("user").await?;
+/// ```
+pub trait Migrate: Cacheable + Sized {
+    /// Convert a payload written with schema `version` to the payload of `version + 1`.
+    ///
+    /// Values stored without a version (that is, not as [`Versioned`])
+    /// are treated as version `0`. It returns an error by default.
+    fn upgrade(version: u32, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let _ = bytes;
+        anyhow::bail!("no migration from version {} of `{}`", version, Self::type_name())
+    }
+
+    /// Decode a payload written with an older schema `version`.
+    ///
+    /// By default, it applies [`Migrate::upgrade`] from `version` up to the current version,
+    /// then decodes the payload with [`Cacheable::from_bytes`].
+    fn migrate(version: u32, bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut payload = bytes.to_vec();
+        for version in version..Self::schema_version() {
+            payload = Self::upgrade(version, &payload)?;
+        }
+
+        Self::from_bytes(&payload)
+    }
+}
+
+/// A value stored together with its schema version.
+///
+/// When decoded, payloads from older versions are upgraded with [`Migrate::migrate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Versioned<T> {
+    value: T,
+    version: u32,
+}
+
+impl<T: Migrate> Versioned<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            version: T::schema_version(),
+        }
+    }
+
+    /// The version the value was stored with.
+    pub fn stored_version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns `true` if the value was upgraded from an older version.
+    pub fn is_migrated(&self) -> bool {
+        self.version != T::schema_version()
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: Migrate> Cacheable for Versioned<T> {
+    fn to_bytes(&self) -> Vec<u8> {
+        let payload = self.value.to_bytes();
+
+        let mut wtr = Vec::with_capacity(MAGIC.len() + 4 + payload.len());
+        wtr.extend_from_slice(&MAGIC);
+        wtr.write_u32::<BigEndian>(T::schema_version()).unwrap();
+        wtr.extend_from_slice(&payload);
+
+        wtr
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let (version, payload) = match bytes.strip_prefix(&MAGIC) {
+            Some(rest) => {
+                let mut rdr = Cursor::new(rest);
+                let version = rdr.read_u32::<BigEndian>()?;
+                (version, &rest[4..])
+            },
+            None => (0, bytes),
+        };
+
+        let current = T::schema_version();
+        let value = if version == current {
+            T::from_bytes(payload)?
+        } else if version < current {
+            T::migrate(version, payload)?
+        } else {
+            anyhow::bail!(
+                "`{}` was stored with version {}, which is newer than the current version {}",
+                T::type_name(), version, current,
+            );
+        };
+
+        Ok(Self { value, version })
+    }
+
+    fn type_name() -> &'static str {
+        T::type_name()
+    }
+
+    fn schema_version() -> u32 {
+        T::schema_version()
+    }
+
+    fn accepts_schema_version(version: u32) -> bool {
+        version <= T::schema_version()
+    }
+}
+
+/// An encoded [`Versioned`] value, written back by [`MigrateExt::get_migrated`].
+///
+/// It has the type name and the schema version of `Versioned<T>`, so that wrappers
+/// such as [`crate::TypeTagged`] tag it the same way.
+#[derive(Debug)]
+struct Upgraded<T> {
+    bytes: Vec<u8>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Migrate> Upgraded<T> {
+    fn new(versioned: &Versioned<T>) -> Self {
+        Self {
+            bytes: versioned.to_bytes(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Migrate> Cacheable for Upgraded<T> {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+
+    fn from_bytes(_bytes: &[u8]) -> anyhow::Result<Self> {
+        anyhow::bail!("`Upgraded` is only written")
+    }
+
+    fn type_name() -> &'static str {
+        Versioned::<T>::type_name()
+    }
+
+    fn schema_version() -> u32 {
+        Versioned::<T>::schema_version()
+    }
+}
+
+/// Extension methods for storing [`Migrate`] values in any [`Cache`].
+#[async_trait::async_trait]
+pub trait MigrateExt: Cache {
+    /// Get a value stored by [`MigrateExt::set_versioned`], and write it back
+    /// in the current version if it was upgraded from an older one.
+    ///
+    /// A plain `get::<Versioned<T>>` upgrades older payloads too, without writing them back.
+    async fn get_migrated<T: Migrate + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>>;
+
+    /// Set a value together with its current schema version.
+    async fn set_versioned<T: Migrate + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()>;
+}
+
+#[async_trait::async_trait]
+impl<C: Cache + Send + Sync> MigrateExt for C {
+    async fn get_migrated<T: Migrate + Send + Sync>(&self, key: impl CacheKey + Send + Sync) -> anyhow::Result<Option<T>> {
+        let key = key.to_key();
+        let Some(versioned) = self.get::<Versioned<T>>(&key).await? else {
+            return Ok(None);
+        };
+
+        if versioned.is_migrated() {
+            let versioned = Versioned::new(versioned.into_inner());
+            self.set(&key, Upgraded::<T>::new(&versioned)).await?;
+            return Ok(Some(versioned.into_inner()));
+        }
+
+        Ok(Some(versioned.into_inner()))
+    }
+
+    async fn set_versioned<T: Migrate + Send + Sync>(&self, key: impl CacheKey + Send + Sync, value: T) -> anyhow::Result<()> {
+        self.set(key, Versioned::new(value)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MemoryCache;
+    use super::*;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct User {
+        name: String,
+        age: u8,
+        email: String,
+    }
+
+    impl Cacheable for User {
+        fn to_bytes(&self) -> Vec<u8> {
+            serde_json::to_vec(self).unwrap()
+        }
+
+        fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+            Ok(serde_json::from_slice(bytes)?)
+        }
+
+        fn schema_version() -> u32 {
+            2
+        }
+    }
+
+    impl Migrate for User {
+        fn upgrade(version: u32, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+            let mut user: serde_json::Value = serde_json::from_slice(bytes)?;
+            match version {
+                0 => user["age"] = 0.into(),
+                1 => user["email"] = "unknown".into(),
+                _ => anyhow::bail!("unknown version {}", version),
+            }
+            Ok(serde_json::to_vec(&user)?)
+        }
+    }
+
+    fn jack(age: u8, email: &str) -> User {
+        User { name: String::from("jack"), age, email: String::from(email) }
+    }
+
+    #[tokio::test]
+    async fn test_versioned() -> anyhow::Result<()> {
+        let cache = MemoryCache::default();
+
+        cache.set_versioned("user", jack(18, "jack@example.com")).await?;
+        assert_eq!(cache.get_migrated::<User>("user").await?, Some(jack(18, "jack@example.com")));
+
+        let versioned = cache.get::<Versioned<User>>("user").await?.unwrap();
+        assert_eq!(versioned.stored_version(), 2);
+        assert!(!versioned.is_migrated());
+
+        assert_eq!(cache.get_migrated::<User>("none").await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrate() -> anyhow::Result<()> {
+        let cache = MemoryCache::default();
+        // written before `age` was added, without a version.
+        cache.set("v0", br#"{"name":"jack"}"#.to_vec()).await?;
+        // written before `email` was added.
+        let mut v1 = MAGIC.to_vec();
+        v1.write_u32::<BigEndian>(1)?;
+        v1.extend_from_slice(br#"{"name":"jack","age":18}"#);
+        cache.set("v1", v1).await?;
+
+        // a plain get upgrades old payloads step by step, without writing them back.
+        let versioned = cache.get::<Versioned<User>>("v0").await?.unwrap();
+        assert_eq!(versioned.stored_version(), 0);
+        assert_eq!(versioned.into_inner(), jack(0, "unknown"));
+        assert_eq!(cache.get::<Versioned<User>>("v1").await?.unwrap().into_inner(), jack(18, "unknown"));
+        assert_eq!(cache.get::<Versioned<User>>("v0").await?.unwrap().stored_version(), 0);
+
+        assert_eq!(cache.get_migrated::<User>("v0").await?, Some(jack(0, "unknown")));
+        assert_eq!(cache.get::<Versioned<User>>("v0").await?.unwrap().stored_version(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrate_type_tagged() -> anyhow::Result<()> {
+        use crate::TypeTagged;
+
+        let cache = TypeTagged::new(MemoryCache::default());
+        // a value written by version 1, before `email` was added.
+        let mut v1 = MAGIC.to_vec();
+        v1.write_u32::<BigEndian>(1)?;
+        v1.extend_from_slice(br#"{"name":"jack","age":18}"#);
+        let mut tagged = vec![0xca, 0xa7, 0x7a, 0x60];
+        tagged.write_u16::<BigEndian>(User::type_name().len() as u16)?;
+        tagged.extend_from_slice(User::type_name().as_bytes());
+        tagged.write_u32::<BigEndian>(1)?;
+        tagged.extend_from_slice(&v1);
+        cache.inner().set("user", tagged).await?;
+
+        assert_eq!(cache.get::<Versioned<User>>("user").await?.unwrap().into_inner(), jack(18, "unknown"));
+        assert_eq!(cache.get_migrated::<User>("user").await?, Some(jack(18, "unknown")));
+        // written back with the current version, and tagged as `Versioned<User>`.
+        let versioned = cache.get::<Versioned<User>>("user").await?.unwrap();
+        assert_eq!(versioned.stored_version(), 2);
+        assert_eq!(cache.get_migrated::<User>("user").await?, Some(jack(18, "unknown")));
+
+        // a plain `User` doesn't accept older versions.
+        assert!(cache.get::<User>("user").await.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_newer_version() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.write_u32::<BigEndian>(3).unwrap();
+        bytes.extend_from_slice(br#"{"name":"jack","age":1,"email":""}"#);
+
+        assert!(Versioned::<User>::from_bytes(&bytes).is_err());
+    }
+}